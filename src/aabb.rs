@@ -12,6 +12,14 @@ impl AABB {
         AABB { min, max }
     }
 
+    // Flat primitives like triangles, rectangles and boxes without thickness have no extent
+    // along some axis, which the slab test in hit never reports as a hit. Their boxes are grown
+    // a bit so they still do.
+    pub fn padded(min: Vec3A, max: Vec3A) -> AABB {
+        let pad = Vec3A::splat(0.0001);
+        AABB::new(min - pad, max + pad)
+    }
+
     pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
         for axis in 0..3 {
             let r_origin = ray.origin()[axis];
@@ -1,7 +1,3 @@
-use std::cmp::Ordering;
-
-use rand::{thread_rng, Rng};
-
 use crate::{
     aabb::AABB,
     hittable::{HitRecord, Hittable},
@@ -23,15 +19,13 @@ pub struct Bvh {
 impl Bvh {
     pub fn new(mut objects: Vec<Box<dyn Hittable>>, t0: f32, t1: f32) -> Bvh {
         fn axis_range(axis: usize, objs: &[Box<dyn Hittable>], t0: f32, t1: f32) -> f32 {
-            let range = objs
-                .iter()
-                .fold((std::f32::MAX, std::f32::MIN), |range, o| {
-                    let bb = o.bounding_box(t0, t1);
-                    let min = bb.min[axis].min(bb.max[axis]);
-                    let max = bb.min[axis].max(bb.max[axis]);
-
-                    (range.0.min(min), range.1.max(max))
-                });
+            let range = objs.iter().fold((f32::MAX, f32::MIN), |range, o| {
+                let bb = o.bounding_box(t0, t1);
+                let min = bb.min[axis].min(bb.max[axis]);
+                let max = bb.min[axis].max(bb.max[axis]);
+
+                (range.0.min(min), range.1.max(max))
+            });
 
             range.1 - range.0
         }
@@ -1,9 +1,9 @@
 use crate::ray::Ray;
+use crate::sampler::{concentric_disk, Sampler};
 use glam::Vec3A;
-use rand;
-use rand::Rng;
 use std;
 
+#[derive(Clone, Debug)]
 pub struct Camera {
     lower_left_corner: Vec3A,
     horizontal: Vec3A,
@@ -14,25 +14,20 @@ pub struct Camera {
     time_1: f32,
     u: Vec3A,
     v: Vec3A,
-    w: Vec3A,
+    // What the camera was built from, so it can be moved and rebuilt.
+    look_from: Vec3A,
+    look_at: Vec3A,
+    up: Vec3A,
+    v_fov: f32,
+    aspect: f32,
+    aperture: f32,
+    focus_dist: f32,
 }
 
 pub const PI: f32 = std::f64::consts::PI as f32;
 
-fn random_in_unit_disk() -> Vec3A {
-    let mut rng = rand::thread_rng();
-
-    loop {
-        let p = 2.0 * Vec3A::new(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0), 0.0)
-            - Vec3A::new(1.0, 1.0, 0.0);
-
-        if Vec3A::dot(p, p) < 1.0 {
-            return p;
-        }
-    }
-}
-
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vec3A,
         look_at: Vec3A,
@@ -63,21 +58,90 @@ impl Camera {
             time_0: t0,
             time_1: t1,
             lens_radius: aperture / 2.0,
-            w: w,
-            u: u,
-            v: v,
+            u,
+            v,
+            look_from,
+            look_at,
+            up,
+            v_fov,
+            aspect,
+            aperture,
+            focus_dist,
         }
     }
 
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk();
+    fn rebuilt(self) -> Camera {
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            self.up,
+            self.v_fov,
+            self.aspect,
+            self.aperture,
+            self.focus_dist,
+            self.time_0,
+            self.time_1,
+        )
+    }
+
+    pub fn with_look(mut self, look_from: Vec3A, look_at: Vec3A) -> Camera {
+        self.look_from = look_from;
+        self.look_at = look_at;
+        self.rebuilt()
+    }
+
+    pub fn with_v_fov(mut self, v_fov: f32) -> Camera {
+        self.v_fov = v_fov;
+        self.rebuilt()
+    }
+
+    pub fn with_aperture(mut self, aperture: f32) -> Camera {
+        self.aperture = aperture;
+        self.rebuilt()
+    }
+
+    pub fn with_focus_dist(mut self, focus_dist: f32) -> Camera {
+        self.focus_dist = focus_dist;
+        self.rebuilt()
+    }
+
+    pub fn look_from(&self) -> Vec3A {
+        self.look_from
+    }
+
+    pub fn look_at(&self) -> Vec3A {
+        self.look_at
+    }
+
+    pub fn up(&self) -> Vec3A {
+        self.up
+    }
+
+    pub fn v_fov(&self) -> f32 {
+        self.v_fov
+    }
+
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn focus_dist(&self) -> f32 {
+        self.focus_dist
+    }
+
+    pub fn shutter(&self) -> (f32, f32) {
+        (self.time_0, self.time_1)
+    }
+
+    pub fn get_ray(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let rd = self.lens_radius * concentric_disk(sampler.next_2d());
         let offset = self.u * rd.x + self.v * rd.y;
+        let time = self.time_0 + sampler.next_1d() * (self.time_1 - self.time_0);
 
-        let mut rng = rand::thread_rng();
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-            rng.gen_range(self.time_0, self.time_1),
+            time,
         )
     }
 }
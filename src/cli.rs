@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+#[cfg(feature = "preview")]
+use std::time::Duration;
+
+use clap::Parser;
+
+use raytracing_test::framebuffer::ImageFormat;
+use raytracing_test::sampler::SamplerKind;
+use raytracing_test::tiles::TileOrder;
+use raytracing_test::{AdaptiveSampling, BounceLimits, RenderSettings};
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "A small path tracer, done after \"Raytracing in one weekend\"."
+)]
+pub struct Args {
+    /// Image width in pixels
+    #[arg(long, default_value_t = 640, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: u32,
+
+    /// Image height in pixels
+    #[arg(long, default_value_t = 320, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: u32,
+
+    /// Samples per pixel, the preview window adds one per frame until it reaches this
+    #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples: u32,
+
+    /// Maximum number of diffuse bounces per path
+    #[arg(long, default_value_t = BounceLimits::default().diffuse)]
+    pub diffuse_bounces: u32,
+
+    /// Maximum number of mirror, metal and glass reflections per path
+    #[arg(long, default_value_t = BounceLimits::default().specular)]
+    pub specular_bounces: u32,
+
+    /// Maximum number of refractions through glass per path
+    #[arg(long, default_value_t = BounceLimits::default().transmission)]
+    pub transmission_bounces: u32,
+
+    /// Bounces before paths can be ended early by russian roulette
+    #[arg(long, default_value_t = 3)]
+    pub roulette_depth: u32,
+
+    /// Number of render threads, defaults to one per logical core
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// Seed for the random scene, a random one is picked and printed if omitted
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Scene description (.ron) to render instead of the built-in random scene
+    #[arg(long, value_name = "FILE")]
+    pub scene: Option<PathBuf>,
+
+    /// Write the render to this file (.png, .ppm or .pfm), can be repeated
+    #[arg(short, long = "output", value_name = "FILE", value_parser = parse_output)]
+    pub outputs: Vec<PathBuf>,
+
+    /// How sample positions are picked: independent, stratified, halton or sobol
+    #[arg(long, default_value_t = SamplerKind::default())]
+    pub sampler: SamplerKind,
+
+    /// Size in pixels of the square tiles the image is rendered in
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tile_size: u32,
+
+    /// Order the tiles are rendered in: scanline, spiral or hilbert
+    #[arg(long, default_value_t = TileOrder::default())]
+    pub tile_order: TileOrder,
+
+    /// Render without opening the preview window
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Stop adding samples in the preview window after this many seconds
+    #[cfg(feature = "preview")]
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds, conflicts_with = "headless")]
+    pub time_limit: Option<Duration>,
+
+    /// Sample noisy pixels adaptively, up to this many samples, --samples is the minimum
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_samples: Option<u32>,
+
+    /// Relative noise at which a pixel stops getting more samples with --max-samples
+    #[arg(long, default_value_t = 0.01, requires = "max_samples")]
+    pub adaptive_threshold: f32,
+
+    /// Write an image of the number of samples per pixel, for --headless renders
+    #[arg(long, value_name = "FILE", value_parser = parse_output)]
+    #[cfg_attr(feature = "preview", arg(requires = "headless"))]
+    pub heatmap: Option<PathBuf>,
+
+    /// Write the albedo of the first diffuse surface each path reaches, for --headless renders
+    #[arg(long, value_name = "FILE", value_parser = parse_output)]
+    #[cfg_attr(feature = "preview", arg(requires = "headless"))]
+    pub albedo: Option<PathBuf>,
+
+    /// Write the normals of the first hits, for --headless renders, use .pfm to keep the signs
+    #[arg(long, value_name = "FILE", value_parser = parse_output)]
+    #[cfg_attr(feature = "preview", arg(requires = "headless"))]
+    pub normal: Option<PathBuf>,
+
+    /// Write the distance to the first hits, for --headless renders
+    #[arg(long, value_name = "FILE", value_parser = parse_output)]
+    #[cfg_attr(feature = "preview", arg(requires = "headless"))]
+    pub depth: Option<PathBuf>,
+}
+
+#[cfg(feature = "preview")]
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{}", e))
+}
+
+// Checked up front so a typo doesn't throw away a finished render.
+fn parse_output(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    match ImageFormat::from_path(&path) {
+        Some(_) => Ok(path),
+        None => Err(ImageFormat::UNSUPPORTED.to_string()),
+    }
+}
+
+impl Args {
+    pub fn render_settings(&self, seed: u64) -> RenderSettings {
+        RenderSettings {
+            width: self.width as usize,
+            height: self.height as usize,
+            samples: self.samples as usize,
+            bounces: BounceLimits {
+                diffuse: self.diffuse_bounces,
+                specular: self.specular_bounces,
+                transmission: self.transmission_bounces,
+            },
+            roulette_depth: self.roulette_depth,
+            seed,
+            first_sample: 0,
+            sampler: self.sampler,
+            tile_size: self.tile_size as usize,
+            tile_order: self.tile_order,
+            adaptive: self.max_samples.map(|max_samples| AdaptiveSampling {
+                max_samples: max_samples as usize,
+                threshold: self.adaptive_threshold,
+            }),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use glam::Vec3A;
+
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::texture::Texture;
+
+// A volume of constant density filling a closed boundary, like fog or smoke. Rays passing
+// through it scatter at an exponentially distributed distance, in a random direction.
+#[derive(Debug)]
+pub struct ConstantMedium<H: Hittable> {
+    boundary: H,
+    neg_inv_density: f32,
+    phase_function: Material,
+}
+
+impl<H: Hittable> ConstantMedium<H> {
+    pub fn new(boundary: H, density: f32, albedo: Arc<Box<dyn Texture>>) -> ConstantMedium<H> {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::Isotropic { albedo },
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for ConstantMedium<H> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        // Where the ray enters and leaves the boundary, even if the ray starts inside it.
+        let enter = self.boundary.hit(ray, f32::NEG_INFINITY, f32::INFINITY)?;
+        let exit = self.boundary.hit(ray, enter.t + 0.0001, f32::INFINITY)?;
+
+        let t_enter = enter.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        // hit doesn't get the pixel's sampler, the renderer draws the number for the distance
+        // with the ray instead. Ray directions are normalized so t is a distance.
+        let distance_inside = t_exit - t_enter;
+        let hit_distance =
+            self.neg_inv_density * (1.0 - ray.scatter_sample()).max(f32::EPSILON).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance;
+        Some(HitRecord {
+            t,
+            p: ray.point_at(t),
+            // Isotropic scattering doesn't look at the normal.
+            normal: Vec3A::new(1.0, 0.0, 0.0),
+            u: 0.0,
+            v: 0.0,
+            material: Some(self.phase_function.clone()),
+        })
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> AABB {
+        self.boundary.bounding_box(t0, t1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rect::BoxShape;
+    use crate::sampler::{IndependentSampler, Sampler};
+    use crate::texture::SolidColor;
+
+    #[test]
+    fn mean_free_path_is_one_over_density() {
+        let albedo: Arc<Box<dyn Texture>> = Arc::new(Box::new(SolidColor::new(Vec3A::ONE)));
+        let boundary = BoxShape::new(
+            Vec3A::splat(-1000.0),
+            Vec3A::splat(1000.0),
+            Material::Isotropic {
+                albedo: albedo.clone(),
+            },
+        );
+        let density = 0.5;
+        let medium = ConstantMedium::new(boundary, density, albedo);
+
+        let mut sampler = IndependentSampler::new(7, 0);
+        let n = 100000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let ray = Ray::new(Vec3A::ZERO, Vec3A::X, 0.0).with_scatter_sample(sampler.next_1d());
+            total += medium.hit(&ray, 0.001, f32::MAX).unwrap().t;
+        }
+        let mean = total / n as f32;
+        assert!(
+            (mean - 1.0 / density).abs() < 0.02,
+            "mean free path {} != {}",
+            mean,
+            1.0 / density
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use glam::Mat3A;
+use minifb::{Key, MouseButton, MouseMode, Window};
+use raytracing_test::Camera;
+
+pub const HELP: &str =
+    "WASD/QE move, drag to orbit, scroll to zoom, [ ] aperture, - = focus distance";
+
+// Fly-through controls for the preview window. Speeds are relative to the distance between
+// look_from and look_at, so they feel the same in scenes of any size.
+#[derive(Default)]
+pub struct CameraControls {
+    last_mouse: Option<(f32, f32)>,
+}
+
+fn axis(window: &Window, negative: Key, positive: Key) -> f32 {
+    let mut value = 0.0;
+    if window.is_key_down(negative) {
+        value -= 1.0;
+    }
+    if window.is_key_down(positive) {
+        value += 1.0;
+    }
+    value
+}
+
+impl CameraControls {
+    // Returns the moved camera, or None when nothing changed. dt is the time since the last
+    // update in seconds.
+    pub fn update(&mut self, window: &Window, camera: &Camera, dt: f32) -> Option<Camera> {
+        let mut look_from = camera.look_from();
+        let mut look_at = camera.look_at();
+        let up = camera.up().normalize();
+        let distance = (look_at - look_from).length();
+        let forward = (look_at - look_from) / distance;
+        let right = forward.cross(up).normalize();
+
+        // WASD moves in the view plane, QE along the up vector.
+        let speed = distance * dt;
+        let movement = axis(window, Key::S, Key::W) * forward
+            + axis(window, Key::A, Key::D) * right
+            + axis(window, Key::Q, Key::E) * up;
+        look_from += speed * movement;
+        look_at += speed * movement;
+
+        // Dragging turns look_from around look_at, without going over the poles.
+        let mouse = window.get_mouse_pos(MouseMode::Discard);
+        if window.get_mouse_down(MouseButton::Left) {
+            if let (Some((x, y)), Some((last_x, last_y))) = (mouse, self.last_mouse) {
+                let (yaw, pitch) = (-(x - last_x) * 0.01, -(y - last_y) * 0.01);
+                let offset = look_from - look_at;
+                let offset = Mat3A::from_axis_angle(up.into(), yaw) * offset;
+                let pitched = Mat3A::from_axis_angle(right.into(), pitch) * offset;
+                let orbit = if pitched.normalize().dot(up).abs() < 0.99 {
+                    pitched
+                } else {
+                    offset
+                };
+                look_from = look_at + orbit;
+            }
+            self.last_mouse = mouse;
+        } else {
+            self.last_mouse = None;
+        }
+
+        let mut v_fov = camera.v_fov();
+        if let Some((_, scroll)) = window.get_scroll_wheel() {
+            v_fov = (v_fov * 0.95f32.powf(scroll)).clamp(1.0, 150.0);
+        }
+
+        let aperture = (camera.aperture()
+            + axis(window, Key::LeftBracket, Key::RightBracket) * 0.05 * distance * dt)
+            .max(0.0);
+        let focus_dist =
+            camera.focus_dist() * (1.0 + axis(window, Key::Minus, Key::Equal) * dt).max(0.1);
+
+        let changed = look_from != camera.look_from()
+            || look_at != camera.look_at()
+            || v_fov != camera.v_fov()
+            || aperture != camera.aperture()
+            || focus_dist != camera.focus_dist();
+        if !changed {
+            return None;
+        }
+
+        Some(
+            camera
+                .clone()
+                .with_look(look_from, look_at)
+                .with_v_fov(v_fov)
+                .with_aperture(aperture)
+                .with_focus_dist(focus_dist),
+        )
+    }
+}
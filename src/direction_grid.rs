@@ -0,0 +1,113 @@
+// Test helpers for checking distributions of directions: a grid of cells over theta and phi,
+// to integrate pdfs on and to count samples in.
+
+use std::f32::consts::PI;
+
+use glam::Vec3A;
+
+pub struct DirectionGrid {
+    rows: usize,
+    columns: usize,
+    // PI for the whole sphere, PI / 2 for the hemisphere around +z.
+    theta_max: f32,
+}
+
+impl DirectionGrid {
+    pub fn sphere(rows: usize, columns: usize) -> DirectionGrid {
+        DirectionGrid {
+            rows,
+            columns,
+            theta_max: PI,
+        }
+    }
+
+    pub fn hemisphere(rows: usize, columns: usize) -> DirectionGrid {
+        DirectionGrid {
+            rows,
+            columns,
+            theta_max: 0.5 * PI,
+        }
+    }
+
+    fn cells(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    // The direction in the middle of cell (i, j), with the solid angle of the cell.
+    fn cell(&self, i: usize, j: usize) -> (Vec3A, f32) {
+        let d_theta = self.theta_max / self.rows as f32;
+        let d_phi = 2.0 * PI / self.columns as f32;
+        let theta = (i as f32 + 0.5) * d_theta;
+        let phi = (j as f32 + 0.5) * d_phi;
+        let dir = Vec3A::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        );
+        (dir, theta.sin() * d_theta * d_phi)
+    }
+
+    // The cell dir falls in, dir is normalized.
+    fn index(&self, dir: Vec3A) -> usize {
+        let theta = dir.z.clamp(-1.0, 1.0).acos();
+        let phi = dir.y.atan2(dir.x).rem_euclid(2.0 * PI);
+        let i = ((theta / self.theta_max * self.rows as f32) as usize).min(self.rows - 1);
+        let j = ((phi / (2.0 * PI) * self.columns as f32) as usize).min(self.columns - 1);
+        i * self.columns + j
+    }
+
+    // The integral of f over the grid, by the midpoint rule.
+    pub fn integrate(&self, f: impl Fn(Vec3A) -> f32) -> f32 {
+        let mut integral = 0.0;
+        for i in 0..self.rows {
+            for j in 0..self.columns {
+                let (dir, solid_angle) = self.cell(i, j);
+                integral += f(dir) * solid_angle;
+            }
+        }
+        integral
+    }
+
+    // Checks that the fraction of samples in each cell matches pdf integrated over the cell,
+    // on a grid fine times finer. samples are normalized, n counts the ones that were dropped
+    // too.
+    pub fn assert_samples_follow(
+        &self,
+        samples: impl Iterator<Item = Vec3A>,
+        n: usize,
+        fine: usize,
+        pdf: impl Fn(Vec3A) -> f32,
+        what: &str,
+    ) {
+        let mut counts = vec![0; self.cells()];
+        for dir in samples {
+            counts[self.index(dir)] += 1;
+        }
+
+        let fine_grid = DirectionGrid {
+            rows: self.rows * fine,
+            columns: self.columns * fine,
+            theta_max: self.theta_max,
+        };
+        let mut expected = vec![0.0; self.cells()];
+        for i in 0..fine_grid.rows {
+            for j in 0..fine_grid.columns {
+                let (dir, solid_angle) = fine_grid.cell(i, j);
+                expected[(i / fine) * self.columns + j / fine] += pdf(dir) * solid_angle;
+            }
+        }
+
+        for (cell, (&count, &expected)) in counts.iter().zip(&expected).enumerate() {
+            let actual = count as f32 / n as f32;
+            assert!(
+                (actual - expected).abs() < 0.004 + 0.05 * expected,
+                "{} cell ({}, {}): {} != {}",
+                what,
+                cell / self.columns,
+                cell % self.columns,
+                actual,
+                expected
+            );
+        }
+    }
+}
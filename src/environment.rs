@@ -0,0 +1,524 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3A};
+
+use crate::helpers::luminance;
+use crate::texture::{ImageTexture, Texture, WrapMode};
+
+// What rays that miss every object see, and what lights the scene from far away.
+pub trait Environment: Send + Sync + core::fmt::Debug {
+    // dir is normalized.
+    fn color(&self, dir: Vec3A) -> Vec3A;
+
+    // Picks a direction towards the bright parts of the environment from a uniform random
+    // point u in the unit square, with its solid angle pdf. None when the environment can't be sampled this way
+    // and is only found by rays that happen to miss the scene.
+    fn sample(&self, _u: Vec2) -> Option<(Vec3A, f32)> {
+        None
+    }
+
+    // The solid angle pdf of sample returning dir.
+    fn pdf(&self, _dir: Vec3A) -> f32 {
+        0.0
+    }
+}
+
+#[derive(Debug)]
+pub struct SolidEnvironment {
+    color: Vec3A,
+}
+
+impl SolidEnvironment {
+    pub fn new(color: Vec3A) -> SolidEnvironment {
+        SolidEnvironment { color }
+    }
+}
+
+impl Environment for SolidEnvironment {
+    fn color(&self, _: Vec3A) -> Vec3A {
+        self.color
+    }
+}
+
+// Blends from bottom, straight down, to top, straight up. The default is the white to blue sky
+// from "Raytracing in one weekend".
+#[derive(Debug)]
+pub struct GradientEnvironment {
+    bottom: Vec3A,
+    top: Vec3A,
+}
+
+impl GradientEnvironment {
+    pub fn new(bottom: Vec3A, top: Vec3A) -> GradientEnvironment {
+        GradientEnvironment { bottom, top }
+    }
+}
+
+impl Default for GradientEnvironment {
+    fn default() -> GradientEnvironment {
+        GradientEnvironment::new(Vec3A::new(1.0, 1.0, 1.0), Vec3A::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for GradientEnvironment {
+    fn color(&self, dir: Vec3A) -> Vec3A {
+        let t = 0.5 * (dir.y + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+// An equirectangular (latitude-longitude) image around the scene, usually an HDR. The middle
+// of the image looks down -z with +y up, rotation turns it around the y axis.
+// Directions are importance sampled by the luminance of the pixels.
+#[derive(Debug)]
+pub struct EnvironmentMap {
+    image: ImageTexture,
+    rotation: f32,
+    intensity: f32,
+    distribution: Option<Distribution2D>,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: ImageTexture) -> EnvironmentMap {
+        let (width, height) = (image.width(), image.height());
+        // Rows near the poles cover less of the sphere.
+        let weights = (0..height)
+            .flat_map(|y| {
+                let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+                let image = &image;
+                (0..width).map(move |x| luminance(image.pixel(x, y)) * sin_theta)
+            })
+            .collect();
+
+        EnvironmentMap {
+            distribution: Distribution2D::new(width, height, weights),
+            // Filtering wraps around the seam but not from one pole to the other.
+            image: image.with_wrap_uv(WrapMode::Repeat, WrapMode::Clamp),
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    // In radians.
+    pub fn with_rotation(mut self, rotation: f32) -> EnvironmentMap {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> EnvironmentMap {
+        self.intensity = intensity;
+        self
+    }
+
+    fn uv(&self, dir: Vec3A) -> (f32, f32) {
+        let phi = f32::atan2(dir.x, -dir.z) - self.rotation;
+        let u = 0.5 + phi / (2.0 * PI);
+        let v = 0.5 + dir.y.clamp(-1.0, 1.0).asin() / PI;
+        (u, v)
+    }
+
+    fn direction(&self, u: f32, v: f32) -> Vec3A {
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation;
+        let elevation = (v - 0.5) * PI;
+        Vec3A::new(
+            elevation.cos() * phi.sin(),
+            elevation.sin(),
+            -elevation.cos() * phi.cos(),
+        )
+    }
+}
+
+// The image's pdf is over its area, the solid angle of a pixel shrinks with sin(theta)
+// towards the poles and the whole image covers 2 pi * pi of theta and phi.
+fn image_to_solid_angle_pdf(density: f32, dir: Vec3A) -> f32 {
+    let sin_theta = (1.0 - dir.y * dir.y).max(0.0).sqrt();
+    if sin_theta <= 0.0 {
+        return 0.0;
+    }
+    density / (2.0 * PI * PI * sin_theta)
+}
+
+impl Environment for EnvironmentMap {
+    fn color(&self, dir: Vec3A) -> Vec3A {
+        let (u, v) = self.uv(dir);
+        self.intensity * self.image.color(u, v, dir)
+    }
+
+    fn sample(&self, u: Vec2) -> Option<(Vec3A, f32)> {
+        let distribution = self.distribution.as_ref()?;
+        let (x, y, density) = distribution.sample(u.x, u.y);
+        let dir = self.direction(
+            x / distribution.width as f32,
+            1.0 - y / distribution.height as f32,
+        );
+
+        let pdf = image_to_solid_angle_pdf(density, dir);
+        if pdf > 0.0 {
+            Some((dir, pdf))
+        } else {
+            None
+        }
+    }
+
+    fn pdf(&self, dir: Vec3A) -> f32 {
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => return 0.0,
+        };
+
+        let (u, v) = self.uv(dir);
+        let (width, height) = (distribution.width, distribution.height);
+        let x = (u.rem_euclid(1.0) * width as f32) as usize;
+        let y = ((1.0 - v) * height as f32) as usize;
+        let density = distribution.density(x.min(width - 1), y.min(height - 1));
+        image_to_solid_angle_pdf(density, dir)
+    }
+}
+
+// A piecewise constant distribution over a grid of weights, sampled by picking a row from the
+// marginal cdf and then a column from that row's conditional cdf.
+#[derive(Debug)]
+struct Distribution2D {
+    width: usize,
+    height: usize,
+    weights: Vec<f32>,
+    total: f32,
+    // height + 1 entries.
+    marginal: Vec<f32>,
+    // height rows of width + 1 entries.
+    conditional: Vec<f32>,
+}
+
+impl Distribution2D {
+    // None when every weight is zero.
+    fn new(width: usize, height: usize, weights: Vec<f32>) -> Option<Distribution2D> {
+        let mut marginal = Vec::with_capacity(height + 1);
+        let mut conditional = Vec::with_capacity(height * (width + 1));
+        let mut total = 0.0;
+
+        marginal.push(0.0);
+        for row in weights.chunks(width) {
+            let row_total: f32 = row.iter().sum();
+            let mut sum = 0.0;
+            conditional.push(0.0);
+            for (x, weight) in row.iter().enumerate() {
+                sum += weight;
+                conditional.push(if row_total > 0.0 {
+                    sum / row_total
+                } else {
+                    (x + 1) as f32 / width as f32
+                });
+            }
+
+            total += row_total;
+            marginal.push(total);
+        }
+
+        if total <= 0.0 {
+            return None;
+        }
+        for cdf in marginal.iter_mut() {
+            *cdf /= total;
+        }
+
+        Some(Distribution2D {
+            width,
+            height,
+            weights,
+            total,
+            marginal,
+            conditional,
+        })
+    }
+
+    // Returns continuous x, y in [0, width) x [0, height) and the density there, relative
+    // to a uniform distribution over the grid.
+    fn sample(&self, u: f32, v: f32) -> (f32, f32, f32) {
+        let (y, dy) = sample_cdf(&self.marginal, v);
+        let row = &self.conditional[y * (self.width + 1)..(y + 1) * (self.width + 1)];
+        let (x, dx) = sample_cdf(row, u);
+        (x as f32 + dx, y as f32 + dy, self.density(x, y))
+    }
+
+    fn density(&self, x: usize, y: usize) -> f32 {
+        self.weights[y * self.width + x] / self.total * (self.width * self.height) as f32
+    }
+}
+
+// Finds the bucket of cdf u falls in, and how far into that bucket it is.
+fn sample_cdf(cdf: &[f32], u: f32) -> (usize, f32) {
+    let buckets = cdf.len() - 1;
+    let i = cdf
+        .partition_point(|&c| c <= u)
+        .saturating_sub(1)
+        .min(buckets - 1);
+    let width = cdf[i + 1] - cdf[i];
+    let offset = if width > 0.0 {
+        ((u - cdf[i]) / width).clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+    (i, offset)
+}
+
+// The Preetham et al. analytic daylight model: "A Practical Analytic Model for Daylight".
+// Turbidity goes from about 2 for a clear sky to 10 for a hazy one. The sky is normalized so
+// the zenith has a luminance of intensity. The sun itself is only drawn when sun_intensity is
+// set, a tiny bright disc is very noisy without sampling it directly.
+#[derive(Debug)]
+pub struct PhysicalSky {
+    sun_direction: Vec3A,
+    intensity: f32,
+    sun_cos_radius: f32,
+    sun_intensity: f32,
+    // Relative zenith luminance and chromaticity, already divided by the Perez function at
+    // the zenith.
+    zenith: Vec3A,
+    perez: [[f32; 5]; 3],
+}
+
+impl PhysicalSky {
+    pub fn new(sun_direction: Vec3A, turbidity: f32) -> PhysicalSky {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity;
+        // Keep the sun just above the horizon, the fit breaks down below it.
+        let theta_s = sun_direction.y.clamp(0.01, 1.0).acos();
+
+        let thetas = [theta_s.powi(3), theta_s * theta_s, theta_s, 1.0];
+        let poly = |c: [[f32; 4]; 3]| {
+            let row = |r: [f32; 4]| r.iter().zip(thetas).map(|(a, b)| a * b).sum::<f32>();
+            t * t * row(c[0]) + t * row(c[1]) + row(c[2])
+        };
+        let x = poly([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = poly([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let at_zenith = |c: [f32; 5]| perez_function(c, 1.0, theta_s.cos());
+        PhysicalSky {
+            sun_direction,
+            intensity: 1.0,
+            sun_cos_radius: 0.25f32.to_radians().cos(),
+            sun_intensity: 0.0,
+            zenith: Vec3A::new(
+                1.0 / at_zenith(perez[0]),
+                x / at_zenith(perez[1]),
+                y / at_zenith(perez[2]),
+            ),
+            perez,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> PhysicalSky {
+        self.intensity = intensity;
+        self
+    }
+
+    // radius is the angular radius of the sun disc in degrees.
+    pub fn with_sun(mut self, radius: f32, intensity: f32) -> PhysicalSky {
+        self.sun_cos_radius = radius.to_radians().cos();
+        self.sun_intensity = intensity;
+        self
+    }
+}
+
+fn perez_function(c: [f32; 5], cos_theta: f32, cos_gamma: f32) -> f32 {
+    let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+    (1.0 + c[0] * (c[1] / cos_theta).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_linear_srgb(x: f32, y: f32, luminance: f32) -> Vec3A {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Vec3A::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+    .max(Vec3A::ZERO)
+}
+
+impl Environment for PhysicalSky {
+    fn color(&self, dir: Vec3A) -> Vec3A {
+        let cos_gamma = dir.dot(self.sun_direction);
+        if self.sun_intensity > 0.0 && cos_gamma > self.sun_cos_radius {
+            return Vec3A::splat(self.sun_intensity);
+        }
+
+        // Below the horizon reuses the sky just above it.
+        let cos_theta = dir.y.max(0.01);
+        let luminance = self.zenith.x * perez_function(self.perez[0], cos_theta, cos_gamma);
+        let x = self.zenith.y * perez_function(self.perez[1], cos_theta, cos_gamma);
+        let y = self.zenith.z * perez_function(self.perez[2], cos_theta, cos_gamma);
+
+        self.intensity * xyy_to_linear_srgb(x, y, luminance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::rng::PixelRng;
+
+    // Uneven in both directions, with a bright spot and some black texels.
+    fn test_map() -> EnvironmentMap {
+        let (width, height) = (16, 8);
+        let pixels = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| match (x, y) {
+                    (5, 2) => Vec3A::splat(50.0),
+                    (x, _) if x % 7 == 3 => Vec3A::ZERO,
+                    (x, y) => Vec3A::new(0.1 + x as f32, 1.0 + y as f32, 0.5),
+                })
+            })
+            .collect();
+        EnvironmentMap::new(ImageTexture::new(width, height, pixels)).with_rotation(0.7)
+    }
+
+    // Bright above the horizon and black below it.
+    fn half_lit_map(width: usize, height: usize) -> EnvironmentMap {
+        let pixels = (0..height)
+            .flat_map(|y| {
+                let c = if y < height / 2 { 1.0 } else { 0.0 };
+                (0..width).map(move |_| Vec3A::splat(c))
+            })
+            .collect();
+        EnvironmentMap::new(ImageTexture::new(width, height, pixels))
+    }
+
+    #[test]
+    fn the_poles_do_not_blend_the_top_and_bottom_rows() {
+        let map = half_lit_map(8, 4);
+        assert_eq!(map.color(Vec3A::Y), Vec3A::ONE);
+        assert_eq!(map.color(-Vec3A::Y), Vec3A::ZERO);
+    }
+
+    #[test]
+    fn the_pdf_integrates_to_one_over_the_sphere() {
+        let map = test_map();
+        // Midpoint rule over elevation and azimuth, dA = cos(elevation) d(elevation) d(phi).
+        let (rows, columns) = (400, 800);
+        let (d_elevation, d_phi) = (PI / rows as f32, 2.0 * PI / columns as f32);
+        let mut integral = 0.0;
+        for i in 0..rows {
+            let elevation = -PI / 2.0 + (i as f32 + 0.5) * d_elevation;
+            for j in 0..columns {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let dir = Vec3A::new(
+                    elevation.cos() * phi.sin(),
+                    elevation.sin(),
+                    -elevation.cos() * phi.cos(),
+                );
+                integral += map.pdf(dir) * elevation.cos() * d_elevation * d_phi;
+            }
+        }
+        assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+    }
+
+    #[test]
+    fn sample_returns_the_pdf_of_its_direction() {
+        let map = test_map();
+        let mut rng = PixelRng::from_key(1);
+        for _ in 0..10000 {
+            let (dir, pdf) = match map.sample(Vec2::new(rng.gen(), rng.gen())) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            assert!((dir.length() - 1.0).abs() < 1e-4);
+            let expected = map.pdf(dir);
+            assert!(
+                (pdf - expected).abs() <= 1e-3 * expected,
+                "{:?}: {} != {}",
+                dir,
+                pdf,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn samples_land_in_each_texel_as_often_as_the_pdf_says() {
+        let map = test_map();
+        let distribution = map.distribution.as_ref().unwrap();
+        let (width, height) = (distribution.width, distribution.height);
+
+        let n = 200000;
+        let mut counts = vec![0; width * height];
+        let mut rng = PixelRng::from_key(3);
+        for _ in 0..n {
+            // None right at the poles, where dir.y rounds to +-1.
+            let dir = match map.sample(Vec2::new(rng.gen(), rng.gen())) {
+                Some((dir, _)) => dir,
+                None => continue,
+            };
+            let (u, v) = map.uv(dir);
+            let x = ((u.rem_euclid(1.0) * width as f32) as usize).min(width - 1);
+            let y = (((1.0 - v) * height as f32) as usize).min(height - 1);
+            counts[y * width + x] += 1;
+        }
+
+        // Integrated over a texel the pdf is its share of the total weight.
+        for (i, &count) in counts.iter().enumerate() {
+            let expected = distribution.weights[i] / distribution.total;
+            let actual = count as f32 / n as f32;
+            assert!(
+                (actual - expected).abs() < 0.003 + 0.05 * expected,
+                "texel {}: {} != {}",
+                i,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn a_single_bright_texel_is_always_sampled() {
+        let (width, height) = (8, 4);
+        let mut pixels = vec![Vec3A::ZERO; width * height];
+        pixels[width + 6] = Vec3A::ONE;
+        let map = EnvironmentMap::new(ImageTexture::new(width, height, pixels));
+
+        let mut rng = PixelRng::from_key(2);
+        for _ in 0..1000 {
+            let (dir, pdf) = map.sample(Vec2::new(rng.gen(), rng.gen())).unwrap();
+            let (u, v) = map.uv(dir);
+            let x = (u.rem_euclid(1.0) * width as f32) as usize;
+            let y = ((1.0 - v) * height as f32) as usize;
+            assert_eq!((x, y), (6, 1));
+            assert!(pdf > 0.0);
+        }
+    }
+}
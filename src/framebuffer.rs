@@ -0,0 +1,219 @@
+use glam::Vec3A;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::tiles::Tile;
+
+// The formats Image::save can write, picked by the file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Pfm,
+}
+
+impl ImageFormat {
+    pub const UNSUPPORTED: &'static str = "unsupported output format, expected .png, .ppm or .pfm";
+
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+// Linear radiance, stored top row first.
+#[derive(Clone, Debug)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3A>,
+}
+
+fn to_display(c: f32) -> u8 {
+    // Same gamma 2 curve the window preview has always used.
+    (c.max(0.0).sqrt().min(1.0) * 255.99) as u8
+}
+
+fn to_u32(c: Vec3A) -> u32 {
+    let color_r = to_display(c.x) as u32;
+    let color_g = to_display(c.y) as u32;
+    let color_b = to_display(c.z) as u32;
+    color_r << 16 | color_g << 8 | color_b
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![Vec3A::ZERO; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3A {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Vec3A) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn pixels(&self) -> &[Vec3A] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Vec3A] {
+        &mut self.pixels
+    }
+
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|c| [to_display(c.x), to_display(c.y), to_display(c.z)])
+            .collect()
+    }
+
+    // 0RGB, the layout minifb expects.
+    pub fn to_u32_buffer(&self) -> Vec<u32> {
+        let mut buffer = vec![0; self.pixels.len()];
+        self.write_u32_buffer(&mut buffer);
+        buffer
+    }
+
+    pub fn write_u32_buffer(&self, buffer: &mut [u32]) {
+        for (data, c) in buffer.iter_mut().zip(self.pixels.iter()) {
+            *data = to_u32(*c);
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Png) => self.write_png(path),
+            Some(ImageFormat::Ppm) => self.write_ppm(&mut BufWriter::new(File::create(path)?)),
+            Some(ImageFormat::Pfm) => self.write_pfm(&mut BufWriter::new(File::create(path)?)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: {}", path.display(), ImageFormat::UNSUPPORTED),
+            )),
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        image::save_buffer(
+            path,
+            &self.to_rgb8(),
+            self.width as u32,
+            self.height as u32,
+            image::ColorType::Rgb8,
+        )
+        .map_err(io::Error::other)
+    }
+
+    // Binary (P6) 8 bit PPM, gamma corrected like the PNG output.
+    pub fn write_ppm<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.to_rgb8())?;
+        w.flush()
+    }
+
+    // Linear float PFM. A negative scale marks little endian data and
+    // scanlines are stored bottom to top.
+    pub fn write_pfm<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width).rev() {
+            for c in row {
+                w.write_all(&c.x.to_le_bytes())?;
+                w.write_all(&c.y.to_le_bytes())?;
+                w.write_all(&c.z.to_le_bytes())?;
+            }
+        }
+        w.flush()
+    }
+}
+
+// Sums renders of the same scene so the average keeps improving, for progressive rendering.
+// Sample counts are kept per pixel, so tiles can be added as they finish.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    sum: Image,
+    samples: Vec<usize>,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Accumulator {
+        Accumulator {
+            sum: Image::new(width, height),
+            samples: vec![0; width * height],
+        }
+    }
+
+    // image is the average of `samples` samples per pixel.
+    pub fn add(&mut self, image: &Image, samples: usize) {
+        assert!(
+            image.width == self.sum.width && image.height == self.sum.height,
+            "Accumulator size mismatch"
+        );
+        for (index, c) in image.pixels.iter().enumerate() {
+            self.add_pixel(index, *c, samples);
+        }
+    }
+
+    // pixels are the tile's rows, each the average of `samples` samples.
+    pub fn add_tile(&mut self, tile: &Tile, pixels: &[Vec3A], samples: usize) {
+        for (index, c) in tile.pixel_indices(self.sum.width).zip(pixels) {
+            self.add_pixel(index, *c, samples);
+        }
+    }
+
+    fn add_pixel(&mut self, index: usize, c: Vec3A, samples: usize) {
+        self.sum.pixels[index] += c * samples as f32;
+        self.samples[index] += samples;
+    }
+
+    pub fn reset(&mut self) {
+        self.sum.pixels.fill(Vec3A::ZERO);
+        self.samples.fill(0);
+    }
+
+    // Samples per pixel so far, the fewest any pixel has.
+    pub fn samples(&self) -> usize {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    fn average(&self, index: usize) -> Vec3A {
+        match self.samples[index] {
+            0 => Vec3A::ZERO,
+            n => self.sum.pixels[index] / n as f32,
+        }
+    }
+
+    pub fn image(&self) -> Image {
+        let mut image = Image::new(self.sum.width, self.sum.height);
+        for (index, c) in image.pixels.iter_mut().enumerate() {
+            *c = self.average(index);
+        }
+        image
+    }
+
+    // Only updates the tile's pixels in a buffer laid out like Image::write_u32_buffer.
+    pub fn write_u32_tile(&self, tile: &Tile, buffer: &mut [u32]) {
+        for index in tile.pixel_indices(self.sum.width) {
+            buffer[index] = to_u32(self.average(index));
+        }
+    }
+}
@@ -14,3 +14,15 @@ pub fn refract(v: Vec3A, n: Vec3A, ni_over_nt: f32) -> Option<Vec3A> {
         None
     }
 }
+
+// Relative luminance of a linear sRGB color.
+pub fn luminance(c: Vec3A) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+// Two unit vectors that make an orthonormal basis with the unit vector w.
+pub fn orthonormal_basis(w: Vec3A) -> (Vec3A, Vec3A) {
+    let a = if w.x.abs() > 0.9 { Vec3A::Y } else { Vec3A::X };
+    let v = Vec3A::cross(w, a).normalize();
+    (Vec3A::cross(w, v), v)
+}
@@ -1,7 +1,8 @@
 use crate::aabb::AABB;
 use crate::material::Material;
 use crate::ray::Ray;
-use glam::Vec3A;
+use glam::{Vec2, Vec3A};
+use std::sync::Arc;
 
 pub struct HitRecord {
     pub p: Vec3A,
@@ -12,7 +13,110 @@ pub struct HitRecord {
     pub material: Option<Material>,
 }
 
+// A point picked on the surface of an object, pdf is per unit area.
+pub struct SurfaceSample {
+    pub p: Vec3A,
+    pub normal: Vec3A,
+    pub pdf: f32,
+}
+
 pub trait Hittable: Send + Sync + std::fmt::Debug {
     fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord>;
     fn bounding_box(&self, t0: f32, t1: f32) -> AABB;
+
+    // Picks a point on the surface from u, uniform in [0, 1)^2, so the object can be sampled
+    // directly as a light. None for the shapes that don't support it.
+    fn sample_surface(&self, _u: Vec2) -> Option<SurfaceSample> {
+        None
+    }
+
+    // The pdf per unit area sample_surface picks the point p with normal with.
+    fn surface_pdf(&self, _p: Vec3A, _normal: Vec3A) -> f32 {
+        0.0
+    }
+
+    // The solid angle pdf of random_direction picking dir from origin, dir is normalized.
+    fn pdf_value(&self, origin: Vec3A, dir: Vec3A, time: f32) -> f32 {
+        area_pdf_value(self, origin, dir, time)
+    }
+
+    // A direction from origin towards the object, picked from u, uniform in [0, 1)^2.
+    fn random_direction(&self, origin: Vec3A, u: Vec2) -> Option<Vec3A> {
+        self.sample_surface(u).map(|sample| sample.p - origin)
+    }
+}
+
+// pdf_value for directions picked through sample_surface: the area pdf of the points dir
+// hits, converted to solid angle. All of them count, sample_surface can pick the back of a
+// sphere as well as the front.
+pub fn area_pdf_value<H: Hittable + ?Sized>(
+    object: &H,
+    origin: Vec3A,
+    dir: Vec3A,
+    time: f32,
+) -> f32 {
+    let ray = Ray::new(origin, dir, time);
+    let mut pdf = 0.0;
+    let mut t_min = 0.001;
+    while let Some(rec) = object.hit(&ray, t_min, f32::MAX) {
+        let cosine = Vec3A::dot(rec.normal, ray.dir()).abs();
+        if cosine >= 1e-6 {
+            pdf += object.surface_pdf(rec.p, rec.normal) * rec.t * rec.t / cosine;
+        }
+        t_min = rec.t + 0.001;
+    }
+    pdf
+}
+
+impl<H: Hittable + ?Sized> Hittable for Box<H> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        (**self).hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> AABB {
+        (**self).bounding_box(t0, t1)
+    }
+
+    fn sample_surface(&self, u: Vec2) -> Option<SurfaceSample> {
+        (**self).sample_surface(u)
+    }
+
+    fn surface_pdf(&self, p: Vec3A, normal: Vec3A) -> f32 {
+        (**self).surface_pdf(p, normal)
+    }
+
+    fn pdf_value(&self, origin: Vec3A, dir: Vec3A, time: f32) -> f32 {
+        (**self).pdf_value(origin, dir, time)
+    }
+
+    fn random_direction(&self, origin: Vec3A, u: Vec2) -> Option<Vec3A> {
+        (**self).random_direction(origin, u)
+    }
+}
+
+// Lights are shared between the Bvh and the World's list of lights.
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        (**self).hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> AABB {
+        (**self).bounding_box(t0, t1)
+    }
+
+    fn sample_surface(&self, u: Vec2) -> Option<SurfaceSample> {
+        (**self).sample_surface(u)
+    }
+
+    fn surface_pdf(&self, p: Vec3A, normal: Vec3A) -> f32 {
+        (**self).surface_pdf(p, normal)
+    }
+
+    fn pdf_value(&self, origin: Vec3A, dir: Vec3A, time: f32) -> f32 {
+        (**self).pdf_value(origin, dir, time)
+    }
+
+    fn random_direction(&self, origin: Vec3A, u: Vec2) -> Option<Vec3A> {
+        (**self).random_direction(origin, u)
+    }
 }
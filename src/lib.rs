@@ -0,0 +1,51 @@
+//! A small path tracer, done after "Raytracing in one weekend".
+//!
+//! ```no_run
+//! use raytracing_test::{RenderSettings, Renderer, Scene};
+//!
+//! let settings = RenderSettings::default();
+//! let scene = Scene::load("scenes/three_spheres.ron", &settings).unwrap();
+//! Renderer::render(&scene, &settings).save("render.png").unwrap();
+//! ```
+
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod constant_medium;
+#[cfg(test)]
+mod direction_grid;
+pub mod environment;
+pub mod framebuffer;
+mod helpers;
+pub mod hittable;
+pub mod material;
+mod microfacet;
+pub mod moving_sphere;
+pub mod obj;
+pub mod pdf;
+pub mod perlin;
+pub mod ray;
+pub mod rect;
+pub mod renderer;
+pub mod rng;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod settings;
+pub mod sphere;
+pub mod texture;
+pub mod tiles;
+pub mod transform;
+pub mod triangle;
+pub mod world;
+
+pub use camera::Camera;
+pub use environment::Environment;
+pub use framebuffer::{Accumulator, Image};
+pub use hittable::{HitRecord, Hittable};
+pub use material::Material;
+pub use renderer::{RenderOutput, Renderer, TileProgress};
+pub use scene::Scene;
+pub use scene_file::SceneError;
+pub use settings::{AdaptiveSampling, BounceLimits, RenderSettings};
+pub use world::World;
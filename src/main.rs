@@ -1,236 +1,244 @@
+#[cfg(feature = "preview")]
 extern crate minifb;
-extern crate rand;
-extern crate rayon;
-
-use bvh::Bvh;
-use rayon::prelude::*;
-mod aabb;
-mod bvh;
-mod camera;
-mod helpers;
-mod hittable;
-mod material;
-mod ray;
-mod sphere;
-mod texture;
-mod world;
-
-use camera::Camera;
-use glam::Vec3A;
-use hittable::{HitRecord, Hittable};
-use material::Material;
+
+mod cli;
+#[cfg(feature = "preview")]
+mod controls;
+
+use clap::Parser;
+use cli::Args;
+#[cfg(feature = "preview")]
+use controls::CameraControls;
+#[cfg(feature = "preview")]
 use minifb::{Key, KeyRepeat, Window, WindowOptions};
 use rand::Rng;
-mod moving_sphere;
-use ray::Ray;
-use sphere::Sphere;
-use std::{sync::Arc, thread, time};
-use world::World;
-
-use crate::{
-    moving_sphere::MovingSphere,
-    texture::{CheckerTexture, SolidColor, Texture},
+#[cfg(feature = "preview")]
+use raytracing_test::Accumulator;
+use raytracing_test::{Image, RenderOutput, RenderSettings, Renderer, Scene, SceneError};
+use std::{
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+    time,
 };
+#[cfg(feature = "preview")]
+use std::{sync::mpsc, thread};
 
-const WIDTH: usize = 640;
-const HEIGHT: usize = 320;
-const SAMPLE_COUNT: usize = 5;
-
-fn color_at(ray: &Ray, bvh: &Bvh, depth: u32) -> Vec3A {
-    if let Some(rec) = bvh.hit(ray, 0.001, std::f32::MAX) {
-        let mut scattered = Ray::new(Vec3A::default(), Vec3A::default(), ray.time());
-        let mut attenuation = Vec3A::default();
-        let rec_c = HitRecord {
-            p: rec.p,
-            normal: rec.normal,
-            t: rec.t,
-            u: rec.u,
-            v: rec.v,
-            material: None,
-        };
-        if let Some(material) = rec.material {
-            if depth < 50
-                && material::scatter(&material, ray, &rec_c, &mut attenuation, &mut scattered)
-            {
-                return attenuation * color_at(&scattered, bvh, depth + 1);
-            } else {
-                return Vec3A::new(0.0, 0.0, 0.0);
-            }
-        } else {
-            panic!("No material wtf!");
-        }
-    } else {
-        let t = 0.5 * (ray.dir().y + 1.0);
-        (1.0 - t) * Vec3A::new(1.0, 1.0, 1.0) + t * Vec3A::new(0.5, 0.7, 1.0)
+fn load_scene(settings: &RenderSettings, scene: Option<&Path>) -> Result<Scene, SceneError> {
+    match scene {
+        Some(path) => Scene::load(path, settings),
+        None => Ok(Scene::random_spheres(settings)),
     }
 }
 
-fn generate_scene(buffer: &mut Vec<u32>) {
-    let mut rng = rand::thread_rng();
-    let mut world = World::default();
-    world.add_object(Box::new(Sphere::new(
-        Vec3A::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Material::Lambertian {
-            texture: Arc::new(Box::new(CheckerTexture::new(
-                Vec3A::new(0.2, 0.3, 0.1),
-                Vec3A::new(0.9, 0.9, 0.9),
-            ))),
-        },
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = rng.gen_range::<f32>(0.0, 1.0);
-
-            let center = Vec3A::new(
-                a as f32 + 0.9 * rng.gen_range(0.0, 1.0),
-                0.2 + 0.2 * rng.gen_range::<f32>(0.0, 1.0),
-                b as f32 + 0.9 * rng.gen_range(0.0, 1.0),
-            );
-
-            if (center - Vec3A::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    let r: f32 = rng.gen_range(0.0, 1.0) * rng.gen_range(0.0, 1.0);
-                    let g: f32 = rng.gen_range(0.0, 1.0) * rng.gen_range(0.0, 1.0);
-                    let b: f32 = rng.gen_range(0.0, 1.0) * rng.gen_range(0.0, 1.0);
-                    let center2 = center + 0.4 * Vec3A::new(0.0, rng.gen_range(0.0, 0.5), 0.0);
-                    world.add_object(Box::new(MovingSphere::new(
-                        center,
-                        center2,
-                        0.0,
-                        1.0,
-                        0.2,
-                        Material::Lambertian {
-                            texture: Arc::new(Box::new(SolidColor::new(Vec3A::new(r, g, b)))),
-                        },
-                    )));
-                } else if choose_mat < 0.95 {
-                    let r: f32 = 0.5 * (1.0 + rng.gen_range(0.0, 1.0));
-                    let g: f32 = 0.5 * (1.0 + rng.gen_range(0.0, 1.0));
-                    let b: f32 = 0.5 * (1.0 + rng.gen_range(0.0, 1.0));
-                    world.add_object(Box::new(Sphere::new(
-                        center,
-                        0.2,
-                        Material::Metal {
-                            albedo: Vec3A::new(r, g, b),
-                            fuzz: 0.5 * rng.gen_range(0.0, 1.0),
-                        },
-                    )));
-                } else {
-                    world.add_object(Box::new(Sphere::new(
-                        center,
-                        0.2,
-                        Material::Dielectric { ref_idx: 1.5 },
-                    )));
-                }
-            }
-        }
-    }
+fn load_scene_or_exit(settings: &RenderSettings, scene: Option<&Path>) -> Scene {
+    load_scene(settings, scene).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
 
-    let texture: Arc<Box<dyn Texture>> =
-        Arc::new(Box::new(SolidColor::new(Vec3A::new(0.4, 0.2, 0.1))));
-
-    world.add_object(Box::new(Sphere::new(
-        Vec3A::new(0.0, 1.0, 0.0),
-        1.0,
-        Material::Dielectric { ref_idx: 1.5 },
-    )));
-
-    world.add_object(Box::new(Sphere::new(
-        Vec3A::new(-4.0, 1.0, 0.0),
-        1.0,
-        Material::Lambertian { texture },
-    )));
-
-    world.add_object(Box::new(Sphere::new(
-        Vec3A::new(4.0, 1.0, 0.0),
-        1.0,
-        Material::Metal {
-            albedo: Vec3A::new(0.7, 0.6, 0.5),
-            fuzz: 0.0,
-        },
-    )));
-
-    let look_from = Vec3A::new(12.0, 1.0, 3.0);
-    let look_at = Vec3A::new(1.0, 0.7, -1.0);
-    let apperture = 0.0;
-    let dist_to_focus = 10.0;
-    let camera = Camera::new(
-        look_from,
-        look_at,
-        Vec3A::new(0.0, 1.0, 0.0),
-        20.0,
-        WIDTH as f32 / HEIGHT as f32,
-        apperture,
-        dist_to_focus,
-        0.0,
-        1.0,
+fn print_progress(finished: usize, total: usize, elapsed: time::Duration) {
+    const BAR_WIDTH: usize = 40;
+    let filled = finished * BAR_WIDTH / total;
+    let eta = elapsed.mul_f64((total - finished) as f64 / finished as f64);
+    eprint!(
+        "\r[{}{}] {:3}% {}/{} tiles, ETA {:.1}s  ",
+        "=".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        finished * 100 / total,
+        finished,
+        total,
+        eta.as_secs_f32()
     );
+}
 
-    let bvh = world.generate_bvh(0.0, 1.0);
-    // println!("Bvh: {:#?}", bvh);
-    // panic!("WTF");
-
+fn render(scene: &Scene, settings: &RenderSettings) -> RenderOutput {
     let start = time::Instant::now();
-    let chunk_size = WIDTH;
-
-    //Switch from par_iter_mut() to iter_mut() to compare with the single threaded version.
-    buffer
-        .par_iter_mut()
-        .chunks(chunk_size)
-        .enumerate()
-        .for_each(|(pos, row_data)| {
-            for (local_pos, data) in row_data.into_iter().enumerate() {
-                let pos = pos * chunk_size + local_pos;
-                let x = pos % WIDTH;
-                let y = HEIGHT - pos / WIDTH;
-                let mut total = Vec3A::default();
-                let mut rng = rand::thread_rng();
-                for _ in 0..SAMPLE_COUNT {
-                    let rx = rng.gen_range(0.0, 1.0);
-                    let ry = rng.gen_range(0.0, 1.0);
-                    let u = (x as f32 + rx) / (WIDTH as f32);
-                    let v = (y as f32 + ry) / (HEIGHT as f32);
-                    let r = camera.get_ray(u, v);
-                    total = total + color_at(&r, &bvh, 0);
-                }
-                let fcolor = total / (SAMPLE_COUNT as f32);
-                let fcolor = Vec3A::new(fcolor.x.sqrt(), fcolor.y.sqrt(), fcolor.z.sqrt());
-                let color_r = (fcolor.x * 255.99) as u32;
-                let color_g = (fcolor.y * 255.99) as u32;
-                let color_b = (fcolor.z * 255.99) as u32;
-                *data = color_r << 16 | color_g << 8 | color_b;
-            }
-        });
-    let duration = time::Instant::now() - start;
-    println!("Generate took: {:?}", duration);
+    // Tiles finish out of order on different threads, only ever move the bar forwards.
+    let shown = Mutex::new(0);
+    let output = Renderer::render_tiles(scene, settings, |progress| {
+        let mut shown = shown.lock().unwrap();
+        if progress.finished > *shown {
+            *shown = progress.finished;
+            print_progress(progress.finished, progress.total, start.elapsed());
+        }
+    });
+    eprintln!();
+    println!("Generate took: {:?}", start.elapsed());
+    if settings.adaptive.is_some() {
+        let total: u64 = output.sample_counts.iter().map(|&n| n as u64).sum();
+        println!(
+            "Average samples per pixel: {:.1}",
+            total as f64 / output.sample_counts.len() as f64
+        );
+    }
+    output
 }
 
-fn main() {
-    let mut buffer: Vec<u32> = vec![0; WIDTH * HEIGHT]; //R..G..B..R..G..B
+fn save_outputs(image: &Image, outputs: &[PathBuf]) {
+    for output in outputs {
+        match image.save(output) {
+            Ok(()) => println!("Wrote {}", output.display()),
+            Err(e) => {
+                eprintln!("Failed to write {}: {}", output.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+}
 
+// Renders one sample per pixel per frame and shows the running average, until settings.samples
+// or the time limit is reached. The outputs are written once it's done. Moving the camera starts
+// over.
+#[cfg(feature = "preview")]
+fn run_preview(
+    settings: &RenderSettings,
+    scene: Option<&Path>,
+    outputs: &[PathBuf],
+    time_limit: Option<time::Duration>,
+) {
+    let mut settings = settings.clone();
     let mut window = Window::new(
         "Raytracing on a plane - ESC to exit",
-        WIDTH,
-        HEIGHT,
+        settings.width,
+        settings.height,
         WindowOptions::default(),
     )
     .unwrap_or_else(|e| {
         panic!("{}", e);
     });
 
-    generate_scene(&mut buffer);
+    let mut scene_data = load_scene_or_exit(&settings, scene);
+    let mut accumulator = Accumulator::new(settings.width, settings.height);
+    let mut buffer = vec![0; settings.width * settings.height];
+    let mut start = time::Instant::now();
+    let mut done = false;
+    let mut controls = CameraControls::default();
+    let mut last_frame = time::Instant::now();
+    println!("{}", controls::HELP);
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
         // We unwrap here as we want this code to exit if it fails.
         // Real applications may want to handle this in a different way
+        // Space re-rolls the random scene, or reloads the scene file so it can be edited live.
         if window.is_key_pressed(Key::Space, KeyRepeat::No) {
-            generate_scene(&mut buffer);
+            settings.seed = settings.seed.wrapping_add(1);
+            println!("Seed: {}", settings.seed);
+            match load_scene(&settings, scene) {
+                Ok(new_scene) => {
+                    scene_data = new_scene;
+                    accumulator.reset();
+                    start = time::Instant::now();
+                    done = false;
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        let dt = last_frame.elapsed().as_secs_f32();
+        last_frame = time::Instant::now();
+        if let Some(camera) = controls.update(&window, scene_data.camera(), dt) {
+            scene_data.set_camera(camera);
+            accumulator.reset();
+            start = time::Instant::now();
+            done = false;
         }
 
-        window.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+        if done {
+            window.update();
+            thread::sleep(time::Duration::from_millis(33));
+            continue;
+        }
+
+        // The pass renders on its own thread so tiles can be shown as they finish.
+        let pass_settings = RenderSettings {
+            samples: 1,
+            first_sample: accumulator.samples(),
+            adaptive: None,
+            ..settings.clone()
+        };
+        let (sender, receiver) = mpsc::channel();
+        let pass_scene = &scene_data;
+        let pass_settings = &pass_settings;
+        thread::scope(|s| {
+            s.spawn(move || {
+                Renderer::render_tiles(pass_scene, pass_settings, |progress| {
+                    let _ = sender.send((progress.tile, progress.pixels.to_vec()));
+                });
+            });
+
+            let mut last_update = time::Instant::now();
+            for (tile, pixels) in receiver {
+                accumulator.add_tile(&tile, &pixels, 1);
+                accumulator.write_u32_tile(&tile, &mut buffer);
+                if last_update.elapsed() >= time::Duration::from_millis(33) {
+                    window
+                        .update_with_buffer(&buffer, settings.width, settings.height)
+                        .unwrap();
+                    last_update = time::Instant::now();
+                }
+            }
+        });
+
+        window.set_title(&format!(
+            "Raytracing on a plane - {}/{} spp - ESC to exit",
+            accumulator.samples(),
+            settings.samples
+        ));
+
+        let out_of_time = time_limit.is_some_and(|limit| start.elapsed() >= limit);
+        if accumulator.samples() >= settings.samples || out_of_time {
+            done = true;
+            println!(
+                "Generate took: {:?}, {} spp",
+                start.elapsed(),
+                accumulator.samples()
+            );
+            save_outputs(&accumulator.image(), outputs);
+        }
 
-        thread::sleep(time::Duration::from_millis(33));
+        window
+            .update_with_buffer(&buffer, settings.width, settings.height)
+            .unwrap();
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("Seed: {}", seed);
+    let settings = args.render_settings(seed);
+
+    let mut outputs = args.outputs.clone();
+    if args.headless || !cfg!(feature = "preview") {
+        if outputs.is_empty() {
+            outputs.push(PathBuf::from("render.png"));
+        }
+        let scene = load_scene_or_exit(&settings, args.scene.as_deref());
+        let output = render(&scene, &settings);
+        save_outputs(&output.image, &outputs);
+        let aovs = [
+            (&args.heatmap, output.sample_heatmap()),
+            (&args.albedo, output.albedo),
+            (&args.normal, output.normal),
+            (&args.depth, output.depth),
+        ];
+        for (path, image) in aovs {
+            if let Some(path) = path {
+                save_outputs(&image, std::slice::from_ref(path));
+            }
+        }
+    } else {
+        #[cfg(feature = "preview")]
+        run_preview(&settings, args.scene.as_deref(), &outputs, args.time_limit);
     }
 }
@@ -1,19 +1,88 @@
-use std::rc::Rc;
+use std::f32::consts::PI;
 use std::sync::Arc;
 
 use crate::helpers::*;
 use crate::hittable::HitRecord;
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, Frame, Ggx};
+use crate::pdf::{CosinePdf, Pdf, SpherePdf};
 use crate::ray::Ray;
+use crate::sampler::{uniform_sphere, Sampler};
 use crate::texture::Texture;
 use glam::Vec3A;
-use rand;
-use rand::Rng;
+use serde::Deserialize;
 
 #[derive(Clone, Debug)]
 pub enum Material {
-    Lambertian { texture: Arc<Box<dyn Texture>> },
-    Metal { albedo: Vec3A, fuzz: f32 },
-    Dielectric { ref_idx: f32 },
+    Lambertian {
+        texture: Arc<Box<dyn Texture>>,
+    },
+    Metal {
+        albedo: Vec3A,
+        fuzz: f32,
+    },
+    Dielectric {
+        ref_idx: f32,
+    },
+    DiffuseLight {
+        emit: Arc<Box<dyn Texture>>,
+    },
+    Isotropic {
+        albedo: Arc<Box<dyn Texture>>,
+    },
+    // A GGX microfacet metal with the complex index of refraction eta + i k, see MetalIor for
+    // measured ones. Roughness goes from 0, a mirror, to 1.
+    Conductor {
+        eta: Vec3A,
+        k: Vec3A,
+        roughness: f32,
+    },
+    // Glass with a GGX microfacet surface, frosted as roughness goes up.
+    RoughDielectric {
+        ref_idx: f32,
+        roughness: f32,
+    },
+}
+
+// Complex indices of refraction of common metals, at red, green and blue wavelengths.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum MetalIor {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+    Custom { eta: Vec3A, k: Vec3A },
+}
+
+impl MetalIor {
+    pub fn eta_k(self) -> (Vec3A, Vec3A) {
+        match self {
+            MetalIor::Gold => (
+                Vec3A::new(0.143, 0.374, 1.442),
+                Vec3A::new(3.983, 2.385, 1.603),
+            ),
+            MetalIor::Copper => (
+                Vec3A::new(0.200, 0.924, 1.102),
+                Vec3A::new(3.912, 2.452, 2.142),
+            ),
+            MetalIor::Aluminium => (
+                Vec3A::new(1.657, 0.880, 0.521),
+                Vec3A::new(9.224, 6.270, 4.837),
+            ),
+            MetalIor::Silver => (
+                Vec3A::new(0.155, 0.117, 0.138),
+                Vec3A::new(4.828, 3.122, 2.147),
+            ),
+            MetalIor::Custom { eta, k } => (eta, k),
+        }
+    }
+}
+
+impl Material {
+    pub fn conductor(ior: MetalIor, roughness: f32) -> Material {
+        let (eta, k) = ior.eta_k();
+        Material::Conductor { eta, k, roughness }
+    }
 }
 
 impl Default for Material {
@@ -25,39 +94,74 @@ impl Default for Material {
     }
 }
 
+pub fn emitted(material: &Material, u: f32, v: f32, p: Vec3A) -> Vec3A {
+    match material {
+        Material::DiffuseLight { emit } => emit.color(u, v, p),
+        _ => Vec3A::ZERO,
+    }
+}
+
+// The kinds of bounces the renderer limits separately, see BounceLimits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BounceKind {
+    Diffuse,
+    // Reflections off mirrors, metals and glass.
+    Specular,
+    // Refractions into or out of glass.
+    Transmission,
+}
+
+// What scatter picked: the scattered ray, and how much it carries of the light coming back
+// along it. pdf is the solid angle pdf the direction was picked with, None for mirror like
+// scattering that can't pick it any other way.
+pub struct ScatterRecord {
+    pub attenuation: Vec3A,
+    pub ray: Ray,
+    pub pdf: Option<f32>,
+    pub kind: BounceKind,
+}
+
 pub fn scatter(
     material: &Material,
     ray_in: &Ray,
     hit: &HitRecord,
-    attenuation: &mut Vec3A,
-    scattered: &mut Ray,
-) -> bool {
-    match &material {
-        &Material::Lambertian { texture } => {
-            let target = hit.p + hit.normal + random_point_in_unit_sphere();
-            *scattered = Ray::new(hit.p, target - hit.p, ray_in.time());
-            *attenuation = texture.color(hit.u, hit.v, hit.p);
-            true
-        }
-        &Material::Metal { albedo, fuzz } => {
-            let reflected = reflect(ray_in.dir(), hit.normal);
-            *scattered = Ray::new(
+    sampler: &mut dyn Sampler,
+) -> Option<ScatterRecord> {
+    match material {
+        Material::Lambertian { texture } => {
+            let pdf = CosinePdf::new(facing_normal(ray_in, hit));
+            let dir = pdf.generate(sampler)?;
+            let ray = Ray::new(hit.p, dir, ray_in.time());
+            Some(ScatterRecord {
+                attenuation: texture.color(hit.u, hit.v, hit.p),
+                pdf: Some(pdf.value(ray.dir())),
+                ray,
+                kind: BounceKind::Diffuse,
+            })
+        }
+        Material::Metal { albedo, fuzz } => {
+            let normal = facing_normal(ray_in, hit);
+            let reflected = reflect(ray_in.dir(), normal);
+            let ray = Ray::new(
                 hit.p,
-                reflected + *fuzz * random_point_in_unit_sphere(),
+                reflected + *fuzz * random_point_in_unit_sphere(sampler),
                 ray_in.time(),
             );
-            *attenuation = *albedo;
-
-            Vec3A::dot(scattered.dir(), hit.normal) > 0.0
+            if Vec3A::dot(ray.dir(), normal) <= 0.0 {
+                return None;
+            }
+            Some(ScatterRecord {
+                attenuation: *albedo,
+                ray,
+                pdf: None,
+                kind: BounceKind::Specular,
+            })
         }
-        &Material::Dielectric { ref_idx } => {
+        Material::Dielectric { ref_idx } => {
             let outward_normal;
             let reflected = reflect(ray_in.dir(), hit.normal);
             let ni_over_nt: f32;
             let cosine;
-            let reflect_prob;
-
-            *attenuation = Vec3A::new(1.0, 1.0, 1.0);
 
             if Vec3A::dot(ray_in.dir(), hit.normal) > 0.0 {
                 outward_normal = -hit.normal;
@@ -69,23 +173,264 @@ pub fn scatter(
                 cosine = -Vec3A::dot(ray_in.dir(), hit.normal) / ray_in.dir().length();
             }
             let refracted = refract(ray_in.dir(), outward_normal, ni_over_nt);
-            if refracted.is_some() {
-                reflect_prob = schlick(cosine, *ref_idx);
+            let reflect_prob = if refracted.is_some() {
+                schlick(cosine, *ref_idx)
             } else {
-                reflect_prob = 1.0;
+                1.0
+            };
+
+            let random_number = sampler.next_1d();
+
+            let (dir, kind) = match refracted {
+                Some(refracted) if random_number >= reflect_prob => {
+                    (refracted, BounceKind::Transmission)
+                }
+                _ => (reflected, BounceKind::Specular),
+            };
+            Some(ScatterRecord {
+                attenuation: Vec3A::ONE,
+                ray: Ray::new(hit.p, dir, ray_in.time()),
+                pdf: None,
+                kind,
+            })
+        }
+        Material::DiffuseLight { .. } => None,
+        Material::Isotropic { albedo } => {
+            let dir = SpherePdf.generate(sampler)?;
+            Some(ScatterRecord {
+                attenuation: albedo.color(hit.u, hit.v, hit.p),
+                ray: Ray::new(hit.p, dir, ray_in.time()),
+                pdf: Some(SpherePdf.value(dir)),
+                kind: BounceKind::Diffuse,
+            })
+        }
+        Material::Conductor { eta, k, roughness } => {
+            let ggx = Ggx::new(*roughness);
+            let (frame, wo) = shading_frame(ray_in, facing_normal(ray_in, hit));
+            if ggx.is_smooth() {
+                return Some(ScatterRecord {
+                    attenuation: fresnel_conductor(wo.z, *eta, *k),
+                    ray: Ray::new(hit.p, frame.to_world(reflect(-wo, Vec3A::Z)), ray_in.time()),
+                    pdf: None,
+                    kind: BounceKind::Specular,
+                });
+            }
+
+            // Only the microfacets seen from wo are picked, see Ggx::sample_visible_normal.
+            let wm = ggx.sample_visible_normal(wo, sampler.next_2d());
+            let wi = reflect(-wo, wm);
+            let pdf = conductor_pdf(&ggx, wo, wi);
+            if wi.z <= 0.0 || pdf <= 0.0 {
+                return None;
+            }
+            Some(ScatterRecord {
+                attenuation: conductor_scattering(&ggx, *eta, *k, wo, wi) / pdf,
+                ray: Ray::new(hit.p, frame.to_world(wi), ray_in.time()),
+                pdf: Some(pdf),
+                kind: BounceKind::Specular,
+            })
+        }
+        Material::RoughDielectric { ref_idx, roughness } => {
+            // Index matched, light goes straight through without bending or reflecting.
+            if is_index_matched(*ref_idx) {
+                return Some(ScatterRecord {
+                    attenuation: Vec3A::ONE,
+                    ray: Ray::new(hit.p, ray_in.dir(), ray_in.time()),
+                    pdf: None,
+                    kind: BounceKind::Transmission,
+                });
             }
 
-            let mut rng = rand::thread_rng();
-            let random_number = rng.gen_range(0.0, 1.0);
+            let ggx = Ggx::new(*roughness);
+            if ggx.is_smooth() {
+                let smooth = Material::Dielectric { ref_idx: *ref_idx };
+                return scatter(&smooth, ray_in, hit, sampler);
+            }
 
-            if random_number < reflect_prob {
-                *scattered = Ray::new(hit.p, reflected, ray_in.time());
+            let (frame, wo) = shading_frame(ray_in, facing_normal(ray_in, hit));
+            let eta = relative_ior(ray_in, hit, *ref_idx);
+            let wm = ggx.sample_visible_normal(wo, sampler.next_2d());
+            let cos_o = wo.dot(wm);
+            let (wi, kind) = if sampler.next_1d() < fresnel_dielectric(cos_o, eta) {
+                (reflect(-wo, wm), BounceKind::Specular)
             } else {
-                *scattered = Ray::new(hit.p, refracted.unwrap(), ray_in.time());
+                let sin2_t = (1.0 - cos_o * cos_o) / (eta * eta);
+                let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+                (
+                    -wo / eta + (cos_o / eta - cos_t) * wm,
+                    BounceKind::Transmission,
+                )
+            };
+
+            let pdf = rough_dielectric_pdf(&ggx, eta, wo, wi);
+            if pdf <= 0.0 || (wi.z > 0.0) != (kind == BounceKind::Specular) {
+                return None;
             }
+            let attenuation = rough_dielectric_scattering(&ggx, eta, wo, wi) / pdf;
+            Some(ScatterRecord {
+                attenuation: Vec3A::splat(attenuation),
+                ray: Ray::new(hit.p, frame.to_world(wi), ray_in.time()),
+                pdf: Some(pdf),
+                kind,
+            })
+        }
+    }
+}
+
+// The BSDF times the cosine at the surface for light arriving from dir and leaving back along
+// ray_in, for the materials scattering_pdf is Some for.
+pub fn scattering(material: &Material, ray_in: &Ray, hit: &HitRecord, dir: Vec3A) -> Vec3A {
+    match material {
+        Material::Lambertian { texture } => {
+            let cosine = Vec3A::dot(facing_normal(ray_in, hit), dir).max(0.0);
+            texture.color(hit.u, hit.v, hit.p) * cosine / PI
+        }
+        Material::Isotropic { albedo } => albedo.color(hit.u, hit.v, hit.p) / (4.0 * PI),
+        Material::Conductor { eta, k, roughness } => {
+            let (frame, wo) = shading_frame(ray_in, facing_normal(ray_in, hit));
+            conductor_scattering(&Ggx::new(*roughness), *eta, *k, wo, frame.to_local(dir))
+        }
+        Material::RoughDielectric { ref_idx, roughness } => {
+            let (frame, wo) = shading_frame(ray_in, facing_normal(ray_in, hit));
+            let eta = relative_ior(ray_in, hit, *ref_idx);
+            let wi = frame.to_local(dir);
+            Vec3A::splat(rough_dielectric_scattering(
+                &Ggx::new(*roughness),
+                eta,
+                wo,
+                wi,
+            ))
+        }
+        _ => Vec3A::ZERO,
+    }
+}
 
-            return true;
+// The pdf scatter picks dir with for materials that scatter in every direction, so their
+// lighting can also be sampled directly and combined with multiple importance sampling.
+// None for mirrors, glass and lights whose scattered direction can't be picked any other way.
+pub fn scattering_pdf(
+    material: &Material,
+    ray_in: &Ray,
+    hit: &HitRecord,
+    dir: Vec3A,
+) -> Option<f32> {
+    match material {
+        Material::Lambertian { .. } => Some(CosinePdf::new(facing_normal(ray_in, hit)).value(dir)),
+        Material::Isotropic { .. } => Some(SpherePdf.value(dir)),
+        Material::Conductor { roughness, .. } => {
+            let ggx = Ggx::new(*roughness);
+            if ggx.is_smooth() {
+                return None;
+            }
+            let (frame, wo) = shading_frame(ray_in, facing_normal(ray_in, hit));
+            Some(conductor_pdf(&ggx, wo, frame.to_local(dir)))
+        }
+        Material::RoughDielectric { ref_idx, roughness } => {
+            let ggx = Ggx::new(*roughness);
+            if ggx.is_smooth() || is_index_matched(*ref_idx) {
+                return None;
+            }
+            let (frame, wo) = shading_frame(ray_in, facing_normal(ray_in, hit));
+            let eta = relative_ior(ray_in, hit, *ref_idx);
+            Some(rough_dielectric_pdf(&ggx, eta, wo, frame.to_local(dir)))
         }
+        _ => None,
+    }
+}
+
+// The frame around normal the microfacet materials work in, and the direction back along
+// ray_in in it.
+fn shading_frame(ray_in: &Ray, normal: Vec3A) -> (Frame, Vec3A) {
+    let frame = Frame::new(normal);
+    let wo = frame.to_local(-ray_in.dir());
+    (frame, wo)
+}
+
+// Glass with about the index of refraction of the air around it, refraction through it is
+// singular as the half vector between wo and wi vanishes.
+fn is_index_matched(ref_idx: f32) -> bool {
+    (ref_idx - 1.0).abs() < 1e-4
+}
+
+// The index of refraction on the far side of the surface over the one on ray_in's side.
+fn relative_ior(ray_in: &Ray, hit: &HitRecord, ref_idx: f32) -> f32 {
+    if Vec3A::dot(ray_in.dir(), hit.normal) > 0.0 {
+        1.0 / ref_idx
+    } else {
+        ref_idx
+    }
+}
+
+// wo and wi are in the shading frame, pointing away from the surface.
+fn conductor_scattering(ggx: &Ggx, eta: Vec3A, k: Vec3A, wo: Vec3A, wi: Vec3A) -> Vec3A {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return Vec3A::ZERO;
+    }
+    let wm = (wo + wi).normalize();
+    fresnel_conductor(wo.dot(wm), eta, k) * ggx.d(wm) * ggx.g(wo, wi) / (4.0 * wo.z)
+}
+
+fn conductor_pdf(ggx: &Ggx, wo: Vec3A, wi: Vec3A) -> f32 {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return 0.0;
+    }
+    let wm = (wo + wi).normalize();
+    ggx.visible_normal_pdf(wo, wm) / (4.0 * wo.dot(wm))
+}
+
+// The microfacet normal that reflects or refracts wo into wi, None when that would be a
+// microfacet facing away from either of them. wi is below the surface for refraction.
+fn dielectric_half_vector(eta: f32, wo: Vec3A, wi: Vec3A) -> Option<Vec3A> {
+    let wm = if wi.z > 0.0 { wo + wi } else { wo + wi * eta };
+    if wo.z <= 0.0 || wi.z == 0.0 || wm.length_squared() < 1e-12 {
+        return None;
+    }
+    let wm = wm.normalize();
+    let wm = if wm.z < 0.0 { -wm } else { wm };
+    if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) <= 0.0 {
+        return None;
+    }
+    Some(wm)
+}
+
+// After Walter et al. "Microfacet Models for Refraction through Rough Surfaces".
+fn rough_dielectric_scattering(ggx: &Ggx, eta: f32, wo: Vec3A, wi: Vec3A) -> f32 {
+    let wm = match dielectric_half_vector(eta, wo, wi) {
+        Some(wm) => wm,
+        None => return 0.0,
+    };
+    let fresnel = fresnel_dielectric(wo.dot(wm), eta);
+    let dg = ggx.d(wm) * ggx.g(wo, wi);
+    if wi.z > 0.0 {
+        fresnel * dg / (4.0 * wo.z)
+    } else {
+        let denom = wi.dot(wm) + wo.dot(wm) / eta;
+        (1.0 - fresnel) * dg * (wi.dot(wm) * wo.dot(wm)).abs() / (wo.z * denom * denom)
+    }
+}
+
+fn rough_dielectric_pdf(ggx: &Ggx, eta: f32, wo: Vec3A, wi: Vec3A) -> f32 {
+    let wm = match dielectric_half_vector(eta, wo, wi) {
+        Some(wm) => wm,
+        None => return 0.0,
+    };
+    let fresnel = fresnel_dielectric(wo.dot(wm), eta);
+    let visible = ggx.visible_normal_pdf(wo, wm);
+    if wi.z > 0.0 {
+        fresnel * visible / (4.0 * wo.dot(wm))
+    } else {
+        let denom = wi.dot(wm) + wo.dot(wm) / eta;
+        (1.0 - fresnel) * visible * wi.dot(wm).abs() / (denom * denom)
+    }
+}
+
+// Open surfaces like triangles can be hit from behind, opaque materials
+// scatter off whichever side the ray arrived from.
+fn facing_normal(ray_in: &Ray, hit: &HitRecord) -> Vec3A {
+    if Vec3A::dot(ray_in.dir(), hit.normal) > 0.0 {
+        -hit.normal
+    } else {
+        hit.normal
     }
 }
 
@@ -95,16 +440,170 @@ fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
 }
 
-fn random_point_in_unit_sphere() -> Vec3A {
-    let mut rng = rand::thread_rng();
+// Uniform in the ball, a direction and a radius instead of rejection sampling so it always
+// takes the same sampler dimensions.
+fn random_point_in_unit_sphere(sampler: &mut dyn Sampler) -> Vec3A {
+    let dir = uniform_sphere(sampler.next_2d());
+    dir * sampler.next_1d().cbrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction_grid::DirectionGrid;
+    use crate::sampler::IndependentSampler;
+
+    fn hit() -> HitRecord {
+        HitRecord {
+            p: Vec3A::ZERO,
+            normal: Vec3A::Z,
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            material: None,
+        }
+    }
+
+    // A ray arriving at the hit from above the surface at cos_theta, or from below it when
+    // cos_theta is negative.
+    fn incoming(cos_theta: f32) -> Ray {
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        Ray::new(
+            Vec3A::new(sin_theta, 0.0, cos_theta),
+            Vec3A::new(-sin_theta, 0.0, -cos_theta),
+            0.0,
+        )
+    }
+
+    fn materials() -> Vec<Material> {
+        vec![
+            Material::conductor(MetalIor::Gold, 0.4),
+            Material::conductor(MetalIor::Aluminium, 0.8),
+            Material::RoughDielectric {
+                ref_idx: 1.5,
+                roughness: 0.3,
+            },
+            Material::RoughDielectric {
+                ref_idx: 1.5,
+                roughness: 0.7,
+            },
+        ]
+    }
 
-    loop {
-        let rand_x = rng.gen_range(-1.0, 1.0);
-        let rand_y = rng.gen_range(-1.0, 1.0);
-        let rand_z = rng.gen_range(-1.0, 1.0);
-        let v = Vec3A::new(rand_x, rand_y, rand_z);
-        if v.length_squared() < 1.0 {
-            return v;
+    #[test]
+    fn scatter_returns_the_pdf_and_weight_of_its_direction() {
+        for material in materials() {
+            for cos_theta in [0.2, 0.7, 1.0, -0.5, -0.9] {
+                let (ray, hit) = (incoming(cos_theta), hit());
+                let mut sampler = IndependentSampler::new(2, 0);
+                for _ in 0..2000 {
+                    let rec = match scatter(&material, &ray, &hit, &mut sampler) {
+                        Some(rec) => rec,
+                        None => continue,
+                    };
+                    let dir = rec.ray.dir();
+                    let pdf = rec.pdf.unwrap();
+                    let expected = scattering_pdf(&material, &ray, &hit, dir).unwrap();
+                    assert!(
+                        (pdf - expected).abs() <= 1e-3 * expected,
+                        "{:?} {}: {} != {}",
+                        material,
+                        cos_theta,
+                        pdf,
+                        expected
+                    );
+                    let weight = scattering(&material, &ray, &hit, dir) / pdf;
+                    assert!(
+                        (rec.attenuation - weight).abs().max_element() <= 1e-3,
+                        "{:?} {}: {:?} != {:?}",
+                        material,
+                        cos_theta,
+                        rec.attenuation,
+                        weight
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scattered_directions_follow_the_pdf() {
+        let grid = DirectionGrid::sphere(8, 8);
+        let n = 50000;
+        for material in materials() {
+            for cos_theta in [0.3, 0.9, -0.6] {
+                let (ray, hit) = (incoming(cos_theta), hit());
+                let mut sampler = IndependentSampler::new(3, 0);
+                let samples = (0..n)
+                    .filter_map(|_| scatter(&material, &ray, &hit, &mut sampler))
+                    .map(|rec| rec.ray.dir());
+                grid.assert_samples_follow(
+                    samples,
+                    n,
+                    16,
+                    |dir| scattering_pdf(&material, &ray, &hit, dir).unwrap(),
+                    &format!("{:?} {}", material, cos_theta),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn index_matched_rough_glass_is_invisible() {
+        for (ref_idx, cos_theta) in [(1.0, 0.3), (1.00005, 1.0), (0.99995, -0.6)] {
+            let material = Material::RoughDielectric {
+                ref_idx,
+                roughness: 0.5,
+            };
+            let (ray, hit) = (incoming(cos_theta), hit());
+            let mut sampler = IndependentSampler::new(5, 0);
+            let rec = scatter(&material, &ray, &hit, &mut sampler).unwrap();
+            assert!((rec.ray.dir() - ray.dir()).length() < 1e-6);
+            assert_eq!(rec.attenuation, Vec3A::ONE);
+            assert!(rec.pdf.is_none());
+        }
+    }
+
+    // The average weight of the scattered rays, the fraction of light a uniformly lit
+    // surface reflects and transmits.
+    fn albedo(material: &Material, cos_theta: f32) -> f32 {
+        let (ray, hit) = (incoming(cos_theta), hit());
+        let mut sampler = IndependentSampler::new(4, 0);
+        let n = 100000;
+        let total: f32 = (0..n)
+            .filter_map(|_| scatter(material, &ray, &hit, &mut sampler))
+            .map(|rec| rec.attenuation.max_element())
+            .sum();
+        total / n as f32
+    }
+
+    // Single scattering microfacets lose the light that would bounce between them, more as
+    // roughness goes up, but never gain any.
+    #[test]
+    fn microfacet_materials_do_not_create_energy() {
+        for roughness in [0.1, 0.3, 0.6, 1.0] {
+            // A conductor with eta 0 reflects everything, its Fresnel term is 1.
+            let mirror = Material::Conductor {
+                eta: Vec3A::ZERO,
+                k: Vec3A::ONE,
+                roughness,
+            };
+            let glass = Material::RoughDielectric {
+                ref_idx: 1.5,
+                roughness,
+            };
+            for cos_theta in [0.1, 0.5, 1.0, -0.1, -0.5, -1.0] {
+                for material in [&mirror, &glass] {
+                    if cos_theta < 0.0 && matches!(material, Material::Conductor { .. }) {
+                        continue;
+                    }
+                    let albedo = albedo(material, cos_theta);
+                    assert!(albedo <= 1.01, "{:?} {}: {}", material, cos_theta, albedo);
+                    if roughness <= 0.1 {
+                        assert!(albedo >= 0.99, "{:?} {}: {}", material, cos_theta, albedo);
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,208 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3A};
+
+use crate::helpers::orthonormal_basis;
+
+// A shading frame with z along the normal. The microfacet functions work on directions in it,
+// pointing away from the surface.
+pub struct Frame {
+    x: Vec3A,
+    y: Vec3A,
+    z: Vec3A,
+}
+
+impl Frame {
+    pub fn new(normal: Vec3A) -> Frame {
+        let (x, y) = orthonormal_basis(normal);
+        Frame { x, y, z: normal }
+    }
+
+    pub fn to_local(&self, v: Vec3A) -> Vec3A {
+        Vec3A::new(v.dot(self.x), v.dot(self.y), v.dot(self.z))
+    }
+
+    pub fn to_world(&self, v: Vec3A) -> Vec3A {
+        v.x * self.x + v.y * self.y + v.z * self.z
+    }
+}
+
+// The GGX (Trowbridge-Reitz) distribution of microfacet normals, with Smith height-correlated
+// masking-shadowing. Roughness goes from 0, a mirror, to 1, and is squared for alpha so it
+// looks about linear.
+pub struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    pub fn new(roughness: f32) -> Ggx {
+        Ggx {
+            alpha: roughness * roughness,
+        }
+    }
+
+    // Too smooth to sample without numerical trouble, treated as a perfect mirror instead.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    // The density of microfacet normals wm, per unit solid angle and projected area.
+    pub fn d(&self, wm: Vec3A) -> f32 {
+        if wm.z <= 0.0 {
+            return 0.0;
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let t = wm.z * wm.z * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * t * t)
+    }
+
+    fn lambda(&self, w: Vec3A) -> f32 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    // The fraction of microfacets visible from w.
+    pub fn g1(&self, w: Vec3A) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // The fraction of microfacets visible from both wo and wi.
+    pub fn g(&self, wo: Vec3A, wi: Vec3A) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // The density of the normals sample_visible_normal picks.
+    pub fn visible_normal_pdf(&self, wo: Vec3A, wm: Vec3A) -> f32 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(wm).max(0.0) * self.d(wm) / wo.z
+    }
+
+    // A microfacet normal as seen from wo, after Heitz's "Sampling the GGX Distribution of
+    // Visible Normals". Normals facing away from wo are never picked.
+    pub fn sample_visible_normal(&self, wo: Vec3A, u: Vec2) -> Vec3A {
+        // Stretch the view direction so the distribution becomes a hemisphere.
+        let wh = Vec3A::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalize();
+        let length_squared = wh.x * wh.x + wh.y * wh.y;
+        let t1 = if length_squared > 0.0 {
+            Vec3A::new(-wh.y, wh.x, 0.0) / length_squared.sqrt()
+        } else {
+            Vec3A::X
+        };
+        let t2 = wh.cross(t1);
+
+        // A point on the disk, squashed to the part of the hemisphere seen from wh.
+        let r = u.x.sqrt();
+        let phi = 2.0 * PI * u.y;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + wh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * wh;
+
+        Vec3A::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+// Reflectance of an interface between dielectrics, eta is the inside's index of refraction
+// over the outside's. 1 for total internal reflection.
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// Reflectance of a metal with the complex index of refraction eta + i k, per color channel.
+pub fn fresnel_conductor(cos_i: f32, eta: Vec3A, k: Vec3A) -> Vec3A {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - Vec3A::splat(sin2);
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).max(Vec3A::ZERO);
+    let a2_plus_b2 = Vec3A::new(
+        a2_plus_b2.x.sqrt(),
+        a2_plus_b2.y.sqrt(),
+        a2_plus_b2.z.sqrt(),
+    );
+    let a = 0.5 * (a2_plus_b2 + t0).max(Vec3A::ZERO);
+    let a = Vec3A::new(a.x.sqrt(), a.y.sqrt(), a.z.sqrt());
+
+    let t1 = a2_plus_b2 + Vec3A::splat(cos2);
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + Vec3A::splat(sin2 * sin2);
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rp + rs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction_grid::DirectionGrid;
+    use crate::sampler::{IndependentSampler, Sampler};
+
+    const ROUGHNESSES: [f32; 3] = [0.3, 0.6, 1.0];
+
+    fn view_directions() -> Vec<Vec3A> {
+        [0.05f32, 0.4, 0.9, 1.0]
+            .iter()
+            .map(|&z| Vec3A::new((1.0 - z * z).sqrt(), 0.0, z))
+            .collect()
+    }
+
+    #[test]
+    fn the_visible_normal_pdf_integrates_to_one() {
+        let grid = DirectionGrid::hemisphere(1000, 400);
+        for roughness in ROUGHNESSES {
+            let ggx = Ggx::new(roughness);
+            for wo in view_directions() {
+                let integral = grid.integrate(|wm| ggx.visible_normal_pdf(wo, wm));
+                assert!(
+                    (integral - 1.0).abs() < 0.01,
+                    "roughness {} wo {:?}: {}",
+                    roughness,
+                    wo,
+                    integral
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn visible_normals_are_sampled_with_their_pdf() {
+        let grid = DirectionGrid::hemisphere(8, 16);
+        let n = 100000;
+        for roughness in ROUGHNESSES {
+            let ggx = Ggx::new(roughness);
+            for wo in view_directions() {
+                let mut sampler = IndependentSampler::new(1, 0);
+                let samples = (0..n).map(|_| {
+                    let wm = ggx.sample_visible_normal(wo, sampler.next_2d());
+                    assert!(wm.z > 0.0 && wm.dot(wo) >= -1e-4, "{:?}", wm);
+                    wm
+                });
+                grid.assert_samples_follow(
+                    samples,
+                    n,
+                    32,
+                    |wm| ggx.visible_normal_pdf(wo, wm),
+                    &format!("roughness {} wo {:?}", roughness, wo),
+                );
+            }
+        }
+    }
+}
@@ -37,8 +37,7 @@ impl MovingSphere {
 
 impl MovingSphere {
     fn center(&self, t: f32) -> Vec3A {
-        return self.center0
-            + ((t - self.t0) / (self.t1 - self.t0)) * (self.center1 - self.center0);
+        self.center0 + ((t - self.t0) / (self.t1 - self.t0)) * (self.center1 - self.center0)
     }
 }
 
@@ -59,11 +58,12 @@ impl Hittable for MovingSphere {
             let temp = (-b - (b * b - a * c).sqrt()) / a;
             if temp < t_max && temp > t_min {
                 let p = ray.point_at(temp);
-                let (u, v) = Sphere::get_uv(&p);
+                let normal = (p - self.center(ray.time())) / self.radius;
+                let (u, v) = Sphere::get_uv(&normal);
                 return Some(HitRecord {
                     t: temp,
                     p,
-                    normal: (p - self.center(ray.time())) / self.radius,
+                    normal,
                     u,
                     v,
                     material: Some(self.material.clone()),
@@ -73,13 +73,14 @@ impl Hittable for MovingSphere {
             let temp = (-b + (b * b - a * c).sqrt()) / a;
             if temp < t_max && temp > t_min {
                 let p = ray.point_at(temp);
-                let (u, v) = Sphere::get_uv(&p);
+                let normal = (p - self.center(ray.time())) / self.radius;
+                let (u, v) = Sphere::get_uv(&normal);
                 return Some(HitRecord {
                     t: temp,
                     p,
                     u,
                     v,
-                    normal: (p - self.center(ray.time())) / self.radius,
+                    normal,
                     material: Some(self.material.clone()),
                 });
             }
@@ -0,0 +1,851 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3A};
+
+use crate::helpers::luminance;
+use crate::material::Material;
+use crate::texture::{ImageTexture, SolidColor, Texture};
+use crate::triangle::TriangleMesh;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// Something in the file that was understood but can't be represented, reported once per
+// kind with the line it was first seen on.
+#[derive(Debug)]
+pub struct ObjWarning {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+    pub count: usize,
+}
+
+impl fmt::Display for ObjWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)?;
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    pub name: String,
+    pub positions: Vec<Vec3A>,
+    pub normals: Vec<Vec3A>,
+    pub uvs: Vec<Vec2>,
+    pub indices: Vec<[u32; 3]>,
+    pub material: Material,
+}
+
+impl ObjMesh {
+    pub fn into_triangle_mesh(self) -> TriangleMesh {
+        TriangleMesh::new(
+            self.positions,
+            self.normals,
+            self.uvs,
+            self.indices,
+            self.material,
+        )
+    }
+}
+
+// One mesh per material used in the file.
+#[derive(Debug)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub warnings: Vec<ObjWarning>,
+}
+
+#[derive(Default)]
+struct Warnings {
+    warnings: Vec<ObjWarning>,
+}
+
+impl Warnings {
+    fn add(&mut self, path: &Path, line: usize, message: String) {
+        match self
+            .warnings
+            .iter_mut()
+            .find(|w| w.path == path && w.message == message)
+        {
+            Some(warning) => warning.count += 1,
+            None => self.warnings.push(ObjWarning {
+                path: path.to_path_buf(),
+                line,
+                message,
+                count: 1,
+            }),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|error| ObjError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+// Splits a line into its keyword and arguments, dropping comments.
+fn statement(line: &str) -> Option<(&str, Vec<&str>)> {
+    let line = match line.find('#') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+    let mut words = line.split_whitespace();
+    let keyword = words.next()?;
+    Some((keyword, words.collect()))
+}
+
+fn parse_floats<const N: usize>(
+    path: &Path,
+    line: usize,
+    keyword: &str,
+    args: &[&str],
+) -> Result<[f32; N], ObjError> {
+    let error = |message: String| ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    if args.len() < N {
+        return Err(error(format!(
+            "`{}` needs {} numbers, found {}",
+            keyword,
+            N,
+            args.len()
+        )));
+    }
+
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| error(format!("`{}`: invalid number `{}`", keyword, arg)))?;
+    }
+    Ok(values)
+}
+
+#[derive(Clone, Debug)]
+struct MtlMaterial {
+    diffuse: Vec3A,
+    specular: Vec3A,
+    shininess: f32,
+    ior: f32,
+    dissolve: f32,
+    emission: Vec3A,
+    illum: u32,
+    diffuse_map: Option<(String, usize)>,
+    transmission_filter: Option<(Vec3A, usize)>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> MtlMaterial {
+        MtlMaterial {
+            diffuse: Vec3A::splat(0.8),
+            specular: Vec3A::ZERO,
+            shininess: 0.0,
+            ior: 1.5,
+            dissolve: 1.0,
+            emission: Vec3A::ZERO,
+            illum: 2,
+            diffuse_map: None,
+            transmission_filter: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Picks the closest of the crate's materials:
+    //  - anything with a Ke emission becomes a DiffuseLight,
+    //  - transparent materials (d, Tr or illum 4, 6, 7, 9) become Dielectric with Ni. The
+    //    Tf filter doesn't make a material transparent, exporters write Tf 1 1 1 on opaque
+    //    ones too, and a colored one is dropped with a warning as Dielectric is clear,
+    //  - reflective ones (illum 3, or Ks brighter than Kd) become Metal with Ks as albedo and
+    //    the fuzz derived from the Phong exponent Ns,
+    //  - everything else is Lambertian with Kd.
+    // path is the MTL file the material came from, for warnings.
+    fn to_material(&self, path: &Path, warnings: &mut Warnings) -> Material {
+        if self.emission != Vec3A::ZERO {
+            return Material::DiffuseLight {
+                emit: Arc::new(Box::new(SolidColor::new(self.emission))),
+            };
+        }
+
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        if transparent {
+            if let Some((filter, line)) = self.transmission_filter {
+                if filter != Vec3A::ONE {
+                    warnings.add(
+                        path,
+                        line,
+                        "colored `Tf` is not supported, glass is clear".to_string(),
+                    );
+                }
+            }
+            return Material::Dielectric { ref_idx: self.ior };
+        }
+
+        if self.illum == 3 || luminance(self.specular) > luminance(self.diffuse) {
+            return Material::Metal {
+                albedo: self.specular,
+                fuzz: (2.0 / (self.shininess + 2.0)).sqrt().min(1.0),
+            };
+        }
+
+        Material::Lambertian {
+            texture: self.diffuse_texture(path, warnings),
+        }
+    }
+
+    // map_Kd is relative to the MTL file, Kd is used when the image can't be loaded.
+    fn diffuse_texture(&self, path: &Path, warnings: &mut Warnings) -> Arc<Box<dyn Texture>> {
+        if let Some((map, map_line)) = &self.diffuse_map {
+            let map_path = path.parent().unwrap_or_else(|| Path::new("")).join(map);
+            match ImageTexture::load(&map_path) {
+                Ok(texture) => return Arc::new(Box::new(texture)),
+                Err(error) => warnings.add(
+                    path,
+                    *map_line,
+                    format!("can't load texture `{}`, using Kd: {}", map, error),
+                ),
+            }
+        }
+
+        Arc::new(Box::new(SolidColor::new(self.diffuse)))
+    }
+}
+
+struct MtlEntry {
+    material: MtlMaterial,
+    path: PathBuf,
+}
+
+fn parse_mtl(
+    path: &Path,
+    source: &str,
+    materials: &mut HashMap<String, MtlEntry>,
+    warnings: &mut Warnings,
+) -> Result<(), ObjError> {
+    let mut current: Option<String> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let (keyword, args) = match statement(line) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = args.join(" ");
+            materials.insert(
+                name.clone(),
+                MtlEntry {
+                    material: MtlMaterial::default(),
+                    path: path.to_path_buf(),
+                },
+            );
+            current = Some(name);
+            continue;
+        }
+
+        let material = match current.as_ref().and_then(|name| materials.get_mut(name)) {
+            Some(entry) => &mut entry.material,
+            None => {
+                return Err(ObjError::Parse {
+                    path: path.to_path_buf(),
+                    line: line_number,
+                    message: format!("`{}` before any `newmtl`", keyword),
+                })
+            }
+        };
+
+        let vec3 = |args: &[&str]| -> Result<Vec3A, ObjError> {
+            // A single value is a grey.
+            if args.len() == 1 {
+                let [v] = parse_floats::<1>(path, line_number, keyword, args)?;
+                Ok(Vec3A::splat(v))
+            } else {
+                Ok(Vec3A::from(parse_floats::<3>(
+                    path,
+                    line_number,
+                    keyword,
+                    args,
+                )?))
+            }
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = vec3(&args)?,
+            "Ks" => material.specular = vec3(&args)?,
+            "Ke" => material.emission = vec3(&args)?,
+            "Tf" => material.transmission_filter = Some((vec3(&args)?, line_number)),
+            // Ambient light has no meaning in a path tracer, everything is lit by the scene.
+            "Ka" => {}
+            "Ns" => material.shininess = parse_floats::<1>(path, line_number, keyword, &args)?[0],
+            "Ni" => material.ior = parse_floats::<1>(path, line_number, keyword, &args)?[0],
+            "d" => material.dissolve = parse_floats::<1>(path, line_number, keyword, &args)?[0],
+            "Tr" => {
+                material.dissolve = 1.0 - parse_floats::<1>(path, line_number, keyword, &args)?[0]
+            }
+            "illum" => {
+                material.illum =
+                    args.first()
+                        .and_then(|a| a.parse().ok())
+                        .ok_or_else(|| ObjError::Parse {
+                            path: path.to_path_buf(),
+                            line: line_number,
+                            message: "`illum` needs an integer".to_string(),
+                        })?
+            }
+            // The file name is the last argument, options like -bm come before it.
+            "map_Kd" => material.diffuse_map = args.last().map(|a| (a.to_string(), line_number)),
+            _ => warnings.add(
+                path,
+                line_number,
+                format!("unsupported statement `{}`", keyword),
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+// Resolves a 1-based, possibly negative (relative to the end) OBJ index.
+fn resolve_index(
+    path: &Path,
+    line: usize,
+    index: &str,
+    len: usize,
+    what: &str,
+) -> Result<usize, ObjError> {
+    let error = |message: String| ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let i: i64 = index
+        .parse()
+        .map_err(|_| error(format!("invalid {} index `{}`", what, index)))?;
+    let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(error(format!(
+            "{} index {} out of range, there are {}",
+            what, i, len
+        )));
+    }
+    Ok(resolved as usize)
+}
+
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct MeshBuilder {
+    material: Option<String>,
+    material_line: usize,
+    vertices: HashMap<VertexKey, u32>,
+    keys: Vec<VertexKey>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, key: VertexKey) -> u32 {
+        let keys = &mut self.keys;
+        *self.vertices.entry(key).or_insert_with(|| {
+            keys.push(key);
+            (keys.len() - 1) as u32
+        })
+    }
+}
+
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    parse_obj(path, &read(path)?, read)
+}
+
+// path is where source came from, mtllib files next to it are read with read_file.
+fn parse_obj<F>(path: &Path, source: &str, read_file: F) -> Result<ObjModel, ObjError>
+where
+    F: Fn(&Path) -> Result<String, ObjError>,
+{
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut warnings = Warnings::default();
+    let mut materials = HashMap::new();
+    let mut positions: Vec<Vec3A> = Vec::new();
+    let mut normals: Vec<Vec3A> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut builders = vec![MeshBuilder::default()];
+    let mut current = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let (keyword, args) = match statement(line) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => positions.push(Vec3A::from(parse_floats::<3>(
+                path,
+                line_number,
+                keyword,
+                &args,
+            )?)),
+            "vn" => normals.push(
+                Vec3A::from(parse_floats::<3>(path, line_number, keyword, &args)?)
+                    .normalize_or_zero(),
+            ),
+            "vt" => {
+                // The v coordinate is optional.
+                let u = parse_floats::<1>(path, line_number, keyword, &args)?[0];
+                let v = if args.len() > 1 {
+                    parse_floats::<2>(path, line_number, keyword, &args)?[1]
+                } else {
+                    0.0
+                };
+                uvs.push(Vec2::new(u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(ObjError::Parse {
+                        path: path.to_path_buf(),
+                        line: line_number,
+                        message: format!("face needs at least 3 vertices, found {}", args.len()),
+                    });
+                }
+
+                let mut face = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut parts = arg.split('/');
+                    let v = resolve_index(
+                        path,
+                        line_number,
+                        parts.next().unwrap_or(""),
+                        positions.len(),
+                        "position",
+                    )?;
+                    let vt = match parts.next() {
+                        Some(vt) if !vt.is_empty() => Some(resolve_index(
+                            path,
+                            line_number,
+                            vt,
+                            uvs.len(),
+                            "texture coordinate",
+                        )?),
+                        _ => None,
+                    };
+                    let vn = match parts.next() {
+                        Some(vn) if !vn.is_empty() => Some(resolve_index(
+                            path,
+                            line_number,
+                            vn,
+                            normals.len(),
+                            "normal",
+                        )?),
+                        _ => None,
+                    };
+                    face.push(builders[current].vertex((v, vt, vn)));
+                }
+
+                // Polygons are split into a triangle fan.
+                for j in 1..face.len() - 1 {
+                    builders[current]
+                        .indices
+                        .push([face[0], face[j], face[j + 1]]);
+                }
+            }
+            "mtllib" => {
+                for file in &args {
+                    let mtl_path = dir.join(file);
+                    let mtl_source = read_file(&mtl_path)?;
+                    parse_mtl(&mtl_path, &mtl_source, &mut materials, &mut warnings)?;
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                current = match builders
+                    .iter()
+                    .position(|b| b.material.as_deref() == Some(name.as_str()))
+                {
+                    Some(index) => index,
+                    None => {
+                        builders.push(MeshBuilder {
+                            material: Some(name),
+                            material_line: line_number,
+                            ..MeshBuilder::default()
+                        });
+                        builders.len() - 1
+                    }
+                };
+            }
+            // Objects and groups don't change how anything renders, meshes are split by material.
+            "o" | "g" => {}
+            _ => warnings.add(
+                path,
+                line_number,
+                format!("unsupported statement `{}`", keyword),
+            ),
+        }
+    }
+
+    let mut meshes = Vec::new();
+    for builder in builders {
+        if builder.indices.is_empty() {
+            continue;
+        }
+
+        let name = builder.material.clone().unwrap_or_default();
+        let material = match &builder.material {
+            None => Material::Lambertian {
+                texture: Arc::new(Box::new(SolidColor::new(MtlMaterial::default().diffuse))),
+            },
+            Some(name) => match materials.get(name) {
+                Some(entry) => entry.material.to_material(&entry.path, &mut warnings),
+                None => {
+                    warnings.add(
+                        path,
+                        builder.material_line,
+                        format!("unknown material `{}`, using the default", name),
+                    );
+                    MtlMaterial::default().to_material(path, &mut warnings)
+                }
+            },
+        };
+
+        // Normals and uvs are only kept when every vertex of the mesh has one.
+        let has_normals = builder.keys.iter().all(|k| k.2.is_some());
+        let has_uvs = builder.keys.iter().all(|k| k.1.is_some());
+        if !has_normals && builder.keys.iter().any(|k| k.2.is_some()) {
+            warnings.add(
+                path,
+                builder.material_line,
+                format!(
+                    "mesh `{}` has normals on only some faces, using flat normals",
+                    name
+                ),
+            );
+        }
+        if !has_uvs && builder.keys.iter().any(|k| k.1.is_some()) {
+            warnings.add(
+                path,
+                builder.material_line,
+                format!(
+                    "mesh `{}` has uvs on only some faces, dropping its uvs",
+                    name
+                ),
+            );
+        }
+
+        meshes.push(ObjMesh {
+            name,
+            positions: builder.keys.iter().map(|k| positions[k.0]).collect(),
+            normals: if has_normals {
+                builder.keys.iter().map(|k| normals[k.2.unwrap()]).collect()
+            } else {
+                Vec::new()
+            },
+            uvs: if has_uvs {
+                builder.keys.iter().map(|k| uvs[k.1.unwrap()]).collect()
+            } else {
+                Vec::new()
+            },
+            indices: builder.indices,
+            material,
+        });
+    }
+
+    if meshes.is_empty() {
+        return Err(ObjError::Parse {
+            path: path.to_path_buf(),
+            line: source.lines().count(),
+            message: "no faces found".to_string(),
+        });
+    }
+
+    Ok(ObjModel {
+        meshes,
+        warnings: warnings.warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses obj as test.obj, with mtl as the test.mtl next to it.
+    fn parse(obj: &str, mtl: &str) -> Result<ObjModel, ObjError> {
+        parse_obj(Path::new("test.obj"), obj, |path| {
+            assert_eq!(path, Path::new("test.mtl"));
+            Ok(mtl.to_string())
+        })
+    }
+
+    fn color(texture: &Arc<Box<dyn Texture>>) -> Vec3A {
+        texture.color(0.0, 0.0, Vec3A::ZERO)
+    }
+
+    const QUAD: &str = "
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+    ";
+
+    #[test]
+    fn polygons_are_split_into_a_fan() {
+        let model = parse(&format!("{}\nf 1 2 3 4", QUAD), "").unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions[2], Vec3A::new(1.0, 1.0, 0.0));
+        assert!(mesh.normals.is_empty());
+        assert!(mesh.uvs.is_empty());
+        assert!(model.warnings.is_empty());
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_current_line() {
+        let obj = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+            v 5 5 5
+            f -4 -1 -2
+        ";
+        let mesh = &parse(obj, "").unwrap().meshes[0];
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 3, 2]]);
+        assert_eq!(mesh.positions[3], Vec3A::splat(5.0));
+    }
+
+    #[test]
+    fn indices_out_of_range_are_errors() {
+        for face in ["f 0 1 2", "f 1 2 5", "f -5 1 2", "f 1/1 2/1 3/1", "f 1 2 x"] {
+            match parse(&format!("{}\n{}", QUAD, face), "") {
+                Err(ObjError::Parse { line, .. }) => assert_eq!(line, 7, "{}", face),
+                other => panic!("{}: expected a parse error, got {:?}", face, other),
+            }
+        }
+    }
+
+    #[test]
+    fn vertices_are_shared_per_position_uv_and_normal() {
+        let obj = format!(
+            "{}
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vn 0 0 2
+            f 1/1/1 2/2/1 3/3/1
+            f 1/1/1 3/3/1 4/1/1",
+            QUAD
+        );
+        let mesh = &parse(&obj, "").unwrap().meshes[0];
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.uvs, vec![Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::ZERO]);
+        // Normals are normalized.
+        assert_eq!(mesh.normals, vec![Vec3A::Z; 4]);
+    }
+
+    #[test]
+    fn normals_without_uvs() {
+        let obj = format!("{}\nvn 0 0 1\nf 1//1 2//1 3//1", QUAD);
+        let mesh = &parse(&obj, "").unwrap().meshes[0];
+        assert_eq!(mesh.normals.len(), 3);
+        assert!(mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn partial_normals_fall_back_to_flat() {
+        let obj = format!("{}\nvn 0 0 1\nf 1//1 2//1 3//1\nf 1 3 4", QUAD);
+        let model = parse(&obj, "").unwrap();
+        assert!(model.meshes[0].normals.is_empty());
+        assert_eq!(model.warnings.len(), 1);
+        assert!(model.warnings[0].message.contains("flat normals"));
+    }
+
+    #[test]
+    fn partial_uvs_are_dropped_with_a_warning() {
+        let obj = format!("{}\nvt 0 0\nf 1/1 2/1 3/1\nf 1 3 4", QUAD);
+        let model = parse(&obj, "").unwrap();
+        assert!(model.meshes[0].uvs.is_empty());
+        assert_eq!(model.warnings.len(), 1);
+        assert!(model.warnings[0].message.contains("dropping its uvs"));
+    }
+
+    #[test]
+    fn meshes_are_split_by_material() {
+        let obj = format!(
+            "mtllib test.mtl
+            {}
+            usemtl red
+            f 1 2 3
+            usemtl light
+            f 1 3 4
+            usemtl red
+            f 2 3 4",
+            QUAD
+        );
+        let mtl = "
+            newmtl red
+            Kd 1 0 0
+            newmtl light
+            Ke 4
+        ";
+        let model = parse(&obj, mtl).unwrap();
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[0].name, "red");
+        assert_eq!(model.meshes[0].indices.len(), 2);
+        match &model.meshes[0].material {
+            Material::Lambertian { texture } => assert_eq!(color(texture), Vec3A::X),
+            other => panic!("expected Lambertian, got {:?}", other),
+        }
+        match &model.meshes[1].material {
+            Material::DiffuseLight { emit } => assert_eq!(color(emit), Vec3A::splat(4.0)),
+            other => panic!("expected DiffuseLight, got {:?}", other),
+        }
+    }
+
+    // Builds one triangle with a material from the MTL statements in body.
+    fn material(body: &str) -> (Material, Vec<ObjWarning>) {
+        let obj = format!("mtllib test.mtl\n{}\nusemtl m\nf 1 2 3", QUAD);
+        let mut model = parse(&obj, &format!("newmtl m\n{}", body)).unwrap();
+        (model.meshes.remove(0).material, model.warnings)
+    }
+
+    #[test]
+    fn transparent_materials_are_dielectric() {
+        for body in ["d 0.5\nNi 1.33", "Tr 0.5\nNi 1.33", "illum 7\nNi 1.33"] {
+            match material(body).0 {
+                Material::Dielectric { ref_idx } => assert_eq!(ref_idx, 1.33, "{}", body),
+                other => panic!("{}: expected Dielectric, got {:?}", body, other),
+            }
+        }
+    }
+
+    #[test]
+    fn only_tinted_glass_warns_about_tf() {
+        let (opaque, warnings) = material("Ka 0.1\nKd 0.5\nTf 1 1 1");
+        assert!(matches!(opaque, Material::Lambertian { .. }));
+        assert!(warnings.is_empty());
+
+        let (glass, warnings) = material("d 0.2\nTf 0.5 1 1");
+        assert!(matches!(glass, Material::Dielectric { .. }));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+        assert!(warnings[0].message.contains("Tf"));
+    }
+
+    #[test]
+    fn reflective_materials_are_metal() {
+        for body in [
+            "Kd 0.1\nKs 0.9 0.8 0.7\nNs 1000",
+            "Kd 1\nKs 0.9 0.8 0.7\nillum 3",
+        ] {
+            match material(body).0 {
+                Material::Metal { albedo, .. } => {
+                    assert_eq!(albedo, Vec3A::new(0.9, 0.8, 0.7), "{}", body)
+                }
+                other => panic!("{}: expected Metal, got {:?}", body, other),
+            }
+        }
+
+        // A dull highlight doesn't make it a metal.
+        match material("Kd 0.5 0.5 0.5\nKs 0.1").0 {
+            Material::Lambertian { texture } => assert_eq!(color(&texture), Vec3A::splat(0.5)),
+            other => panic!("expected Lambertian, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_textures_fall_back_to_kd() {
+        let (material, warnings) = material("Kd 0.2 0.4 0.6\nmap_Kd -bm 1 missing.png");
+        match material {
+            Material::Lambertian { texture } => {
+                assert_eq!(color(&texture), Vec3A::new(0.2, 0.4, 0.6))
+            }
+            other => panic!("expected Lambertian, got {:?}", other),
+        }
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, Path::new("test.mtl"));
+        assert_eq!(warnings[0].line, 3);
+        assert!(warnings[0].message.contains("missing.png"));
+    }
+
+    #[test]
+    fn warnings_are_counted_once_per_kind() {
+        let obj = format!(
+            "{}
+            s 1
+            f 1 2 3
+            s off
+            l 1 2
+            usemtl nowhere
+            f 1 3 4",
+            QUAD
+        );
+        let model = parse(&obj, "").unwrap();
+        let messages: Vec<_> = model
+            .warnings
+            .iter()
+            .map(|w| (w.line, w.message.as_str(), w.count))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (7, "unsupported statement `s`", 2),
+                (10, "unsupported statement `l`", 1),
+                (11, "unknown material `nowhere`, using the default", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_before_newmtl_are_errors() {
+        let obj = format!("mtllib test.mtl\n{}\nf 1 2 3", QUAD);
+        match parse(&obj, "# comment\nKd 1 0 0") {
+            Err(ObjError::Parse { path, line, .. }) => {
+                assert_eq!(path, Path::new("test.mtl"));
+                assert_eq!(line, 2);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+}
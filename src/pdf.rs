@@ -0,0 +1,214 @@
+use std::f32::consts::PI;
+
+use std::sync::Arc;
+
+use glam::Vec3A;
+
+use crate::hittable::Hittable;
+use crate::sampler::{uniform_sphere, Sampler};
+
+// A distribution of directions that can be sampled and evaluated, so the renderer can weigh
+// samples picked by one strategy against the others that could have picked them.
+pub trait Pdf {
+    // The solid angle pdf of generate picking dir, dir is normalized.
+    fn value(&self, dir: Vec3A) -> f32;
+
+    // A direction picked with this pdf, not necessarily normalized. None when there is
+    // nothing to pick.
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3A>;
+}
+
+impl<P: Pdf + ?Sized> Pdf for Box<P> {
+    fn value(&self, dir: Vec3A) -> f32 {
+        (**self).value(dir)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3A> {
+        (**self).generate(sampler)
+    }
+}
+
+// Directions around normal weighted by their cosine, what a diffuse surface scatters into.
+pub struct CosinePdf {
+    normal: Vec3A,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3A) -> CosinePdf {
+        CosinePdf { normal }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, dir: Vec3A) -> f32 {
+        Vec3A::dot(self.normal, dir).max(0.0) / PI
+    }
+
+    // A point on the unit sphere around the normal gives exactly cosine weighted directions.
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3A> {
+        let dir = self.normal + uniform_sphere(sampler.next_2d());
+        if dir.length_squared() < 1e-8 {
+            Some(self.normal)
+        } else {
+            Some(dir)
+        }
+    }
+}
+
+// Every direction equally likely.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _dir: Vec3A) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3A> {
+        Some(uniform_sphere(sampler.next_2d()))
+    }
+}
+
+// Directions from origin towards an object, see Hittable::random_direction.
+pub struct HittablePdf<'a> {
+    object: &'a dyn Hittable,
+    origin: Vec3A,
+    time: f32,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(object: &'a dyn Hittable, origin: Vec3A, time: f32) -> HittablePdf<'a> {
+        HittablePdf {
+            object,
+            origin,
+            time,
+        }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, dir: Vec3A) -> f32 {
+        self.object.pdf_value(self.origin, dir, self.time)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3A> {
+        self.object.random_direction(self.origin, sampler.next_2d())
+    }
+}
+
+// Directions from origin towards one of lights, each equally likely: a mixture of one
+// HittablePdf per light, made as they are needed so nothing is collected at every hit.
+pub struct LightsPdf<'a> {
+    lights: &'a [Arc<dyn Hittable>],
+    origin: Vec3A,
+    time: f32,
+}
+
+impl<'a> LightsPdf<'a> {
+    pub fn new(lights: &'a [Arc<dyn Hittable>], origin: Vec3A, time: f32) -> LightsPdf<'a> {
+        LightsPdf {
+            lights,
+            origin,
+            time,
+        }
+    }
+
+    fn light(&self, light: &'a Arc<dyn Hittable>) -> HittablePdf<'a> {
+        HittablePdf::new(light.as_ref(), self.origin, self.time)
+    }
+}
+
+impl Pdf for LightsPdf<'_> {
+    fn value(&self, dir: Vec3A) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        self.lights
+            .iter()
+            .map(|light| self.light(light).value(dir))
+            .sum::<f32>()
+            / self.lights.len() as f32
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3A> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let n = self.lights.len();
+        let index = ((sampler.next_1d() * n as f32) as usize).min(n - 1);
+        self.light(&self.lights[index]).generate(sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction_grid::DirectionGrid;
+    use crate::material::Material;
+    use crate::rect::XZRect;
+    use crate::sampler::IndependentSampler;
+    use crate::sphere::Sphere;
+
+    fn lights() -> Vec<Arc<dyn Hittable>> {
+        vec![
+            Arc::new(Sphere::new(
+                Vec3A::new(0.0, 0.0, 3.0),
+                1.0,
+                Material::default(),
+            )),
+            Arc::new(XZRect::new(-1.0, 2.0, -1.0, 1.0, -2.0, Material::default())),
+            // Seen from inside, picked by its surface.
+            Arc::new(Sphere::new(
+                Vec3A::new(0.5, 0.0, 0.0),
+                4.0,
+                Material::default(),
+            )),
+        ]
+    }
+
+    // Every pdf, the lights one by one and all of them together, seen from the origin.
+    fn pdfs(lights: &[Arc<dyn Hittable>]) -> Vec<(String, Box<dyn Pdf + '_>)> {
+        let mut pdfs: Vec<(String, Box<dyn Pdf>)> = vec![
+            (
+                "cosine".to_string(),
+                Box::new(CosinePdf::new(Vec3A::new(0.6, 0.0, 0.8))),
+            ),
+            ("sphere".to_string(), Box::new(SpherePdf)),
+            (
+                "lights".to_string(),
+                Box::new(LightsPdf::new(lights, Vec3A::ZERO, 0.0)),
+            ),
+        ];
+        for (i, light) in lights.iter().enumerate() {
+            pdfs.push((
+                format!("light {}", i),
+                Box::new(HittablePdf::new(light.as_ref(), Vec3A::ZERO, 0.0)),
+            ));
+        }
+        pdfs
+    }
+
+    #[test]
+    fn values_integrate_to_one_over_the_sphere() {
+        let lights = lights();
+        let grid = DirectionGrid::sphere(1000, 500);
+        for (name, pdf) in pdfs(&lights) {
+            let integral = grid.integrate(|dir| pdf.value(dir));
+            assert!((integral - 1.0).abs() < 0.01, "{}: {}", name, integral);
+        }
+    }
+
+    #[test]
+    fn generated_directions_follow_the_values() {
+        let lights = lights();
+        let grid = DirectionGrid::sphere(8, 16);
+        let n = 100000;
+        for (name, pdf) in pdfs(&lights) {
+            let mut sampler = IndependentSampler::new(1, 0);
+            let samples: Vec<Vec3A> = (0..n)
+                .filter_map(|_| pdf.generate(&mut sampler))
+                .map(|dir| dir.normalize())
+                .collect();
+            grid.assert_samples_follow(samples.into_iter(), n, 16, |dir| pdf.value(dir), &name);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use glam::Vec3A;
+use rand::{Rng, SeedableRng, StdRng};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise from "Raytracing: the next week". The gradients and permutation
+// tables come from the seed, so the same seed always gives the same pattern.
+#[derive(Debug)]
+pub struct Perlin {
+    gradients: Vec<Vec3A>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = StdRng::from_seed(&[seed as usize]);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3A::new(
+                    rng.gen_range(-1.0, 1.0),
+                    rng.gen_range(-1.0, 1.0),
+                    rng.gen_range(-1.0, 1.0),
+                )
+                .normalize()
+            })
+            .collect();
+
+        Perlin {
+            gradients,
+            perm_x: permutation(&mut rng),
+            perm_y: permutation(&mut rng),
+            perm_z: permutation(&mut rng),
+        }
+    }
+
+    // Smoothly varying noise in about [-1, 1], zero at integer lattice points.
+    pub fn noise(&self, p: Vec3A) -> f32 {
+        let floor = p.floor();
+        let f = p - floor;
+        let (i, j, k) = (floor.x as i64, floor.y as i64, floor.z as i64);
+
+        let mut corners = [[[Vec3A::ZERO; 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[wrap(i + di as i64)]
+                        ^ self.perm_y[wrap(j + dj as i64)]
+                        ^ self.perm_z[wrap(k + dk as i64)];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+
+        interpolate(&corners, f)
+    }
+
+    // Sum of octaves of noise at doubling frequencies and halving weights, as an absolute value.
+    pub fn turbulence(&self, p: Vec3A, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            sum += weight * self.noise(p);
+            weight *= 0.5;
+            p *= 2.0;
+        }
+
+        sum.abs()
+    }
+}
+
+fn wrap(i: i64) -> usize {
+    (i & (POINT_COUNT as i64 - 1)) as usize
+}
+
+fn permutation(rng: &mut StdRng) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+    rng.shuffle(&mut perm);
+    perm
+}
+
+fn interpolate(corners: &[[[Vec3A; 2]; 2]; 2], f: Vec3A) -> f32 {
+    // Hermite smoothing hides the lattice.
+    let s = f * f * (Vec3A::splat(3.0) - 2.0 * f);
+    let mut sum = 0.0;
+
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f32, j as f32, k as f32);
+                let weight = Vec3A::new(f.x - fi, f.y - fj, f.z - fk);
+                sum += (fi * s.x + (1.0 - fi) * (1.0 - s.x))
+                    * (fj * s.y + (1.0 - fj) * (1.0 - s.y))
+                    * (fk * s.z + (1.0 - fk) * (1.0 - s.z))
+                    * gradient.dot(weight);
+            }
+        }
+    }
+
+    sum
+}
@@ -5,6 +5,9 @@ pub struct Ray {
     origin: Vec3A,
     dir: Vec3A,
     time: f32,
+    // A uniform random number from the path's sampler, for objects that decide by chance
+    // where along the ray it is stopped, like ConstantMedium.
+    scatter_sample: f32,
 }
 
 impl Ray {
@@ -13,9 +16,15 @@ impl Ray {
             origin,
             dir: dir.normalize(),
             time,
+            scatter_sample: 0.5,
         }
     }
 
+    pub fn with_scatter_sample(mut self, scatter_sample: f32) -> Ray {
+        self.scatter_sample = scatter_sample;
+        self
+    }
+
     pub fn origin(&self) -> Vec3A {
         self.origin
     }
@@ -28,6 +37,10 @@ impl Ray {
         self.time
     }
 
+    pub fn scatter_sample(&self) -> f32 {
+        self.scatter_sample
+    }
+
     pub fn point_at(&self, t: f32) -> Vec3A {
         self.origin + self.dir * t
     }
@@ -0,0 +1,214 @@
+use glam::{Vec2, Vec3A};
+
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable, SurfaceSample};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::world::World;
+
+// An axis aligned rectangle at `k` along axis `c`, spanning [a0, a1] x [b0, b1] along axes a and b.
+// The normal points along +c, or -c when flipped.
+#[derive(Debug)]
+struct AxisRect {
+    a: usize,
+    b: usize,
+    c: usize,
+    a0: f32,
+    a1: f32,
+    b0: f32,
+    b1: f32,
+    k: f32,
+    flipped: bool,
+    material: Material,
+}
+
+impl AxisRect {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let t = (self.k - ray.origin()[self.c]) / ray.dir()[self.c];
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let p = ray.point_at(t);
+        let (a, b) = (p[self.a], p[self.b]);
+        if a < self.a0 || a > self.a1 || b < self.b0 || b > self.b1 {
+            return None;
+        }
+
+        Some(HitRecord {
+            t,
+            p,
+            normal: self.normal(),
+            u: (a - self.a0) / (self.a1 - self.a0),
+            v: (b - self.b0) / (self.b1 - self.b0),
+            material: Some(self.material.clone()),
+        })
+    }
+
+    fn normal(&self) -> Vec3A {
+        let mut normal = Vec3A::ZERO;
+        normal[self.c] = if self.flipped { -1.0 } else { 1.0 };
+        normal
+    }
+
+    fn area(&self) -> f32 {
+        ((self.a1 - self.a0) * (self.b1 - self.b0)).abs()
+    }
+
+    // A rectangle without area can't be sampled, lights like that are skipped.
+    fn sample_surface(&self, u: Vec2) -> Option<SurfaceSample> {
+        if self.area() <= 0.0 {
+            return None;
+        }
+
+        let mut p = Vec3A::ZERO;
+        p[self.a] = self.a0 + u.x * (self.a1 - self.a0);
+        p[self.b] = self.b0 + u.y * (self.b1 - self.b0);
+        p[self.c] = self.k;
+        Some(SurfaceSample {
+            p,
+            normal: self.normal(),
+            pdf: self.surface_pdf(),
+        })
+    }
+
+    fn surface_pdf(&self) -> f32 {
+        let area = self.area();
+        if area > 0.0 {
+            1.0 / area
+        } else {
+            0.0
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let mut min = Vec3A::ZERO;
+        let mut max = Vec3A::ZERO;
+        min[self.a] = self.a0;
+        max[self.a] = self.a1;
+        min[self.b] = self.b0;
+        max[self.b] = self.b1;
+        min[self.c] = self.k;
+        max[self.c] = self.k;
+        AABB::padded(min, max)
+    }
+}
+
+macro_rules! axis_rect {
+    ($name:ident, $a_axis:expr, $b_axis:expr, $c_axis:expr) => {
+        #[derive(Debug)]
+        pub struct $name {
+            rect: AxisRect,
+        }
+
+        impl $name {
+            pub fn new(a0: f32, a1: f32, b0: f32, b1: f32, k: f32, material: Material) -> $name {
+                $name {
+                    rect: AxisRect {
+                        a: $a_axis,
+                        b: $b_axis,
+                        c: $c_axis,
+                        a0,
+                        a1,
+                        b0,
+                        b1,
+                        k,
+                        flipped: false,
+                        material,
+                    },
+                }
+            }
+
+            // Makes the normal point the other way, for the inside faces of a closed shape.
+            pub fn flipped(mut self) -> $name {
+                self.rect.flipped = !self.rect.flipped;
+                self
+            }
+        }
+
+        impl Hittable for $name {
+            fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+                self.rect.hit(ray, t_min, t_max)
+            }
+
+            fn bounding_box(&self, _: f32, _: f32) -> AABB {
+                self.rect.bounding_box()
+            }
+
+            fn sample_surface(&self, u: Vec2) -> Option<SurfaceSample> {
+                self.rect.sample_surface(u)
+            }
+
+            fn surface_pdf(&self, _p: Vec3A, _normal: Vec3A) -> f32 {
+                self.rect.surface_pdf()
+            }
+        }
+    };
+}
+
+// XYRect::new(x0, x1, y0, y1, z, material) and so on for the other two planes.
+axis_rect!(XYRect, 0, 1, 2);
+axis_rect!(XZRect, 0, 2, 1);
+axis_rect!(YZRect, 1, 2, 0);
+
+// An axis aligned box made of six rectangles with outward facing normals.
+#[derive(Debug)]
+pub struct BoxShape {
+    min: Vec3A,
+    max: Vec3A,
+    sides: World,
+}
+
+impl BoxShape {
+    pub fn new(p0: Vec3A, p1: Vec3A, material: Material) -> BoxShape {
+        let min = p0.min(p1);
+        let max = p0.max(p1);
+        let mut sides = World::default();
+
+        sides.add_object(Box::new(XYRect::new(
+            min.x,
+            max.x,
+            min.y,
+            max.y,
+            max.z,
+            material.clone(),
+        )));
+        sides.add_object(Box::new(
+            XYRect::new(min.x, max.x, min.y, max.y, min.z, material.clone()).flipped(),
+        ));
+        sides.add_object(Box::new(XZRect::new(
+            min.x,
+            max.x,
+            min.z,
+            max.z,
+            max.y,
+            material.clone(),
+        )));
+        sides.add_object(Box::new(
+            XZRect::new(min.x, max.x, min.z, max.z, min.y, material.clone()).flipped(),
+        ));
+        sides.add_object(Box::new(YZRect::new(
+            min.y,
+            max.y,
+            min.z,
+            max.z,
+            max.x,
+            material.clone(),
+        )));
+        sides.add_object(Box::new(
+            YZRect::new(min.y, max.y, min.z, max.z, min.x, material).flipped(),
+        ));
+
+        BoxShape { min, max, sides }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.sides.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> AABB {
+        AABB::padded(self.min, self.max)
+    }
+}
@@ -0,0 +1,476 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use glam::Vec3A;
+use rayon::prelude::*;
+
+use crate::framebuffer::Image;
+use crate::helpers::luminance;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{self, BounceKind, Material};
+use crate::pdf::{LightsPdf, Pdf};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::settings::{BounceLimits, RenderSettings};
+use crate::tiles::{self, Tile};
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+// Directions from origin towards the scene's lights, each light equally likely.
+fn lights_pdf(scene: &Scene, origin: Vec3A, time: f32) -> LightsPdf<'_> {
+    LightsPdf::new(scene.lights(), origin, time)
+}
+
+// Light reaching a hit that isn't mirror like along a direction picked towards the scene's
+// lights, weighted against finding it through the material's scattered ray like
+// sample_environment.
+fn sample_lights(
+    ray: &Ray,
+    scene: &Scene,
+    material: &Material,
+    hit: &HitRecord,
+    sampler: &mut dyn Sampler,
+) -> Vec3A {
+    if scene.lights().is_empty() {
+        return Vec3A::ZERO;
+    }
+
+    let pdf = lights_pdf(scene, hit.p, ray.time());
+    let shadow_ray = match pdf.generate(sampler) {
+        Some(dir) => Ray::new(hit.p, dir, ray.time()).with_scatter_sample(sampler.next_1d()),
+        None => return Vec3A::ZERO,
+    };
+    let light_pdf = pdf.value(shadow_ray.dir());
+    let scattering_pdf =
+        material::scattering_pdf(material, ray, hit, shadow_ray.dir()).unwrap_or(0.0);
+    let scattering = material::scattering(material, ray, hit, shadow_ray.dir());
+    if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return Vec3A::ZERO;
+    }
+
+    // Whatever the shadow ray hits first is what lights the hit from there, usually the light
+    // itself and nothing when something else is in the way.
+    let emitted = match scene.bvh().hit(&shadow_ray, 0.001, f32::MAX) {
+        Some(HitRecord {
+            material: Some(light_material),
+            u,
+            v,
+            p,
+            ..
+        }) => material::emitted(&light_material, u, v, p),
+        _ => return Vec3A::ZERO,
+    };
+
+    let weight = power_heuristic(light_pdf, scattering_pdf);
+    scattering * emitted * weight / light_pdf
+}
+
+// Light from the environment reaching a hit that isn't mirror like, found by sampling the
+// environment directly. Weighted against finding it through the material's own scattered ray,
+// see the miss branch of trace_path.
+fn sample_environment(
+    ray: &Ray,
+    scene: &Scene,
+    material: &Material,
+    hit: &HitRecord,
+    sampler: &mut dyn Sampler,
+) -> Vec3A {
+    let environment = scene.environment();
+    let (dir, light_pdf) = match environment.sample(sampler.next_2d()) {
+        Some(sample) => sample,
+        None => return Vec3A::ZERO,
+    };
+
+    let scattering_pdf = material::scattering_pdf(material, ray, hit, dir).unwrap_or(0.0);
+    let scattering = material::scattering(material, ray, hit, dir);
+    if scattering_pdf <= 0.0 {
+        return Vec3A::ZERO;
+    }
+
+    let shadow_ray = Ray::new(hit.p, dir, ray.time()).with_scatter_sample(sampler.next_1d());
+    if scene.bvh().hit(&shadow_ray, 0.001, f32::MAX).is_some() {
+        return Vec3A::ZERO;
+    }
+
+    let weight = power_heuristic(light_pdf, scattering_pdf);
+    scattering * environment.color(dir) * weight / light_pdf
+}
+
+// How far along a path is: the bounces of each kind it took, and how much of the light found
+// further along it still reaches the camera.
+#[derive(Clone, Copy)]
+struct PathState {
+    depth: u32,
+    diffuse: u32,
+    specular: u32,
+    transmission: u32,
+    throughput: Vec3A,
+}
+
+impl PathState {
+    fn camera() -> PathState {
+        PathState {
+            depth: 0,
+            diffuse: 0,
+            specular: 0,
+            transmission: 0,
+            throughput: Vec3A::ONE,
+        }
+    }
+
+    // The state after one more bounce, None when the path already took all the bounces of that
+    // kind it is allowed.
+    fn bounced(self, kind: BounceKind, attenuation: Vec3A, limits: &BounceLimits) -> Option<Self> {
+        let mut next = self;
+        let (count, limit) = match kind {
+            BounceKind::Diffuse => (&mut next.diffuse, limits.diffuse),
+            BounceKind::Specular => (&mut next.specular, limits.specular),
+            BounceKind::Transmission => (&mut next.transmission, limits.transmission),
+        };
+        if *count >= limit {
+            return None;
+        }
+        *count += 1;
+        next.depth += 1;
+        next.throughput *= attenuation;
+        Some(next)
+    }
+}
+
+// What one path through a pixel found: its radiance, and what it first hit for the AOVs.
+struct PathSample {
+    color: Vec3A,
+    // The color of the first diffuse surface, light or environment the path reached.
+    albedo: Vec3A,
+    // The normal at the first hit, zero when the camera ray missed everything.
+    normal: Vec3A,
+    // The distance to the first hit, zero when the camera ray missed everything.
+    depth: f32,
+}
+
+// Follows the path of the camera ray through the scene, adding up the light it finds weighted
+// by the path's throughput.
+fn trace_path(
+    camera_ray: Ray,
+    scene: &Scene,
+    settings: &RenderSettings,
+    sampler: &mut dyn Sampler,
+) -> PathSample {
+    let mut ray = camera_ray;
+    let mut path = PathState::camera();
+    let mut radiance = Vec3A::ZERO;
+    // The pdf the material picked ray with, None for the camera ray and mirror like bounces.
+    let mut scattering_pdf = None;
+    let (mut albedo, mut normal, mut depth) = (None, Vec3A::ZERO, 0.0);
+
+    loop {
+        ray = ray.with_scatter_sample(sampler.next_1d());
+        let mut rec = match scene.bvh().hit(&ray, 0.001, f32::MAX) {
+            Some(rec) => rec,
+            None => {
+                let environment = scene.environment();
+                let weight = match scattering_pdf {
+                    Some(pdf) => power_heuristic(pdf, environment.pdf(ray.dir())),
+                    None => 1.0,
+                };
+                let color = environment.color(ray.dir());
+                radiance += path.throughput * weight * color;
+                albedo.get_or_insert(color.min(Vec3A::ONE));
+                break;
+            }
+        };
+        let material = rec
+            .material
+            .take()
+            .expect("hit an object without a material");
+        if path.depth == 0 {
+            normal = rec.normal;
+            depth = rec.t;
+        }
+
+        let mut emitted = material::emitted(&material, rec.u, rec.v, rec.p);
+        // Lights found by a bounce that has a pdf were also sampled directly at the last hit.
+        if let Some(pdf) = scattering_pdf {
+            if emitted != Vec3A::ZERO {
+                let light_pdf = lights_pdf(scene, ray.origin(), ray.time()).value(ray.dir());
+                emitted *= power_heuristic(pdf, light_pdf);
+            }
+        }
+        radiance += path.throughput * emitted;
+
+        let scatter = match material::scatter(&material, &ray, &rec, sampler) {
+            Some(scatter) => scatter,
+            None => {
+                albedo.get_or_insert(emitted.min(Vec3A::ONE));
+                break;
+            }
+        };
+        let attenuation = scatter.attenuation;
+        if scatter.kind == BounceKind::Diffuse {
+            albedo.get_or_insert(attenuation);
+        }
+        let mut next = match path.bounced(scatter.kind, attenuation, &settings.bounces) {
+            Some(next) => next,
+            None => break,
+        };
+
+        if scatter.pdf.is_some() {
+            let direct = sample_lights(&ray, scene, &material, &rec, sampler)
+                + sample_environment(&ray, scene, &material, &rec, sampler);
+            radiance += path.throughput * direct;
+        }
+
+        // Russian roulette: dim paths are likely to end here, the ones that survive carry
+        // the light of those that didn't so the average stays the same.
+        if next.depth > settings.roulette_depth {
+            let survival = next.throughput.max_element().min(0.95);
+            if sampler.next_1d() >= survival {
+                break;
+            }
+            next.throughput /= survival;
+        }
+
+        path = next;
+        ray = scatter.ray;
+        scattering_pdf = scatter.pdf;
+    }
+
+    PathSample {
+        color: radiance,
+        albedo: albedo.unwrap_or(Vec3A::ZERO),
+        normal,
+        depth,
+    }
+}
+
+// Whether the mean of n luminance samples, with the given sum and sum of squares, is known
+// well enough.
+fn converged(sum: f32, sum_squared: f32, n: usize, threshold: f32) -> bool {
+    if n < 2 {
+        return false;
+    }
+    let n = n as f32;
+    let mean = sum / n;
+    let variance = ((sum_squared - sum * mean) / (n - 1.0)).max(0.0);
+    (variance / n).sqrt() <= threshold * mean.max(0.01)
+}
+
+struct RenderedPixel {
+    color: Vec3A,
+    albedo: Vec3A,
+    normal: Vec3A,
+    depth: f32,
+    samples: u32,
+}
+
+// The average of the paths through pixel x, row, with row 0 at the top, and how many there were.
+fn render_pixel(scene: &Scene, settings: &RenderSettings, x: usize, row: usize) -> RenderedPixel {
+    let (width, height) = (settings.width, settings.height);
+    let y = height - row;
+    let max_samples = match settings.adaptive {
+        Some(adaptive) => adaptive.max_samples.max(settings.samples),
+        None => settings.samples,
+    };
+
+    let mut total = Vec3A::default();
+    let (mut albedo, mut normal, mut depth) = (Vec3A::ZERO, Vec3A::ZERO, 0.0);
+    let (mut sum, mut sum_squared) = (0.0, 0.0);
+    let mut n = 0;
+    let pixel = (row * width + x) as u64;
+    let mut sampler = settings.sampler.create(settings.seed, pixel, max_samples);
+    while n < max_samples {
+        sampler.start_sample(settings.first_sample + n);
+        let jitter = sampler.next_2d();
+        let u = (x as f32 + jitter.x) / (width as f32);
+        let v = (y as f32 + jitter.y) / (height as f32);
+        let r = scene.camera().get_ray(u, v, sampler.as_mut());
+        let path = trace_path(r, scene, settings, sampler.as_mut());
+        let color = path.color;
+        total += color;
+        albedo += path.albedo;
+        normal += path.normal;
+        depth += path.depth;
+        n += 1;
+
+        if let Some(adaptive) = settings.adaptive {
+            let l = luminance(color);
+            sum += l;
+            sum_squared += l * l;
+            if n >= settings.samples && converged(sum, sum_squared, n, adaptive.threshold) {
+                break;
+            }
+        }
+    }
+    RenderedPixel {
+        color: total / (n as f32),
+        albedo: albedo / (n as f32),
+        normal: normal.normalize_or_zero(),
+        depth: depth / (n as f32),
+        samples: n as u32,
+    }
+}
+
+// Blue for few samples through green and yellow to red for many, t is in [0, 1].
+fn heat_color(t: f32) -> Vec3A {
+    let colors = [
+        Vec3A::new(0.0, 0.0, 0.5),
+        Vec3A::new(0.0, 0.3, 1.0),
+        Vec3A::new(0.0, 1.0, 0.2),
+        Vec3A::new(1.0, 1.0, 0.0),
+        Vec3A::new(1.0, 0.0, 0.0),
+    ];
+    let position = t.clamp(0.0, 1.0) * (colors.len() - 1) as f32;
+    let i = (position as usize).min(colors.len() - 2);
+    colors[i].lerp(colors[i + 1], position - i as f32)
+}
+
+// What Renderer::render_tiles produces.
+#[derive(Debug)]
+pub struct RenderOutput {
+    pub image: Image,
+    // How many samples each pixel got, in the same order as the image's pixels.
+    pub sample_counts: Vec<u32>,
+    // AOVs, for denoisers and compositing. Averages over each pixel's paths of the color of the
+    // first diffuse surface they reached, the normal where they first hit something and the
+    // distance to it in all three channels. Normals go from -1 to 1, only PFM keeps them whole.
+    pub albedo: Image,
+    pub normal: Image,
+    pub depth: Image,
+}
+
+impl RenderOutput {
+    // A debug image of sample_counts, blue for the fewest samples to red for the most.
+    pub fn sample_heatmap(&self) -> Image {
+        let min = self.sample_counts.iter().copied().min().unwrap_or(0);
+        let max = self.sample_counts.iter().copied().max().unwrap_or(0);
+        let range = (max - min).max(1) as f32;
+
+        let mut heatmap = Image::new(self.image.width(), self.image.height());
+        for (c, &count) in heatmap.pixels_mut().iter_mut().zip(&self.sample_counts) {
+            *c = heat_color((count - min) as f32 / range);
+        }
+        heatmap
+    }
+}
+
+// Passed to the callback of Renderer::render_tiles every time a tile is done.
+#[derive(Debug)]
+pub struct TileProgress<'a> {
+    pub tile: Tile,
+    // The tile's pixels, row by row.
+    pub pixels: &'a [Vec3A],
+    // Tiles done so far, including this one.
+    pub finished: usize,
+    pub total: usize,
+}
+
+pub struct Renderer;
+
+impl Renderer {
+    pub fn render(scene: &Scene, settings: &RenderSettings) -> Image {
+        Renderer::render_tiles(scene, settings, |_| {}).image
+    }
+
+    // Renders the image tile by tile in settings.tile_order. on_tile is called from the render
+    // threads as each tile finishes.
+    pub fn render_tiles<F>(scene: &Scene, settings: &RenderSettings, on_tile: F) -> RenderOutput
+    where
+        F: Fn(&TileProgress) + Sync,
+    {
+        let (width, height) = (settings.width, settings.height);
+        let tiles = tiles::tiles(width, height, settings.tile_size, settings.tile_order);
+        let next_tile = AtomicUsize::new(0);
+        let finished = AtomicUsize::new(0);
+
+        // Every worker keeps taking the next tile in order, rayon's own splitting would start
+        // the threads in different parts of the list.
+        let rendered: Vec<Vec<(Tile, Vec<RenderedPixel>)>> = (0..rayon::current_num_threads())
+            .into_par_iter()
+            .map(|_| {
+                let mut done = Vec::new();
+                while let Some(&tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                    let results: Vec<RenderedPixel> = (tile.y..tile.y + tile.height)
+                        .flat_map(|row| {
+                            (tile.x..tile.x + tile.width)
+                                .map(move |x| render_pixel(scene, settings, x, row))
+                        })
+                        .collect();
+                    let pixels: Vec<Vec3A> = results.iter().map(|pixel| pixel.color).collect();
+                    on_tile(&TileProgress {
+                        tile,
+                        pixels: &pixels,
+                        finished: finished.fetch_add(1, Ordering::Relaxed) + 1,
+                        total: tiles.len(),
+                    });
+                    done.push((tile, results));
+                }
+                done
+            })
+            .collect();
+
+        let mut output = RenderOutput {
+            image: Image::new(width, height),
+            sample_counts: vec![0; width * height],
+            albedo: Image::new(width, height),
+            normal: Image::new(width, height),
+            depth: Image::new(width, height),
+        };
+        for (tile, results) in rendered.into_iter().flatten() {
+            for (index, pixel) in tile.pixel_indices(width).zip(results) {
+                output.image.pixels_mut()[index] = pixel.color;
+                output.sample_counts[index] = pixel.samples;
+                output.albedo.pixels_mut()[index] = pixel.albedo;
+                output.normal.pixels_mut()[index] = pixel.normal;
+                output.depth.pixels_mut()[index] = Vec3A::splat(pixel.depth);
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_file;
+    use crate::settings::AdaptiveSampling;
+
+    fn render_with_threads(threads: usize) -> RenderOutput {
+        let settings = RenderSettings {
+            width: 48,
+            height: 32,
+            samples: 4,
+            seed: 3,
+            tile_size: 8,
+            adaptive: Some(AdaptiveSampling {
+                max_samples: 16,
+                threshold: 0.05,
+            }),
+            ..RenderSettings::default()
+        };
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/cornell_smoke.ron");
+        let scene = scene_file::load(path, &settings).unwrap();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| Renderer::render_tiles(&scene, &settings, |_| {}))
+    }
+
+    #[test]
+    fn render_does_not_depend_on_the_thread_count() {
+        let single = render_with_threads(1);
+        let many = render_with_threads(4);
+        assert_eq!(single.image.pixels(), many.image.pixels());
+        assert_eq!(single.sample_counts, many.sample_counts);
+        assert_eq!(single.albedo.pixels(), many.albedo.pixels());
+        assert_eq!(single.normal.pixels(), many.normal.pixels());
+        assert_eq!(single.depth.pixels(), many.depth.pixels());
+    }
+}
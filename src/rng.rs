@@ -0,0 +1,45 @@
+use rand::Rng;
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// The SplitMix64 finalizer, a good 64 bit hash.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// A counter based random number stream: the n-th number is a hash of the key and n. Every
+// sample of every pixel gets its own stream from the seed, so the image doesn't depend on
+// which thread renders which pixel, or in what order.
+#[derive(Clone, Debug)]
+pub struct PixelRng {
+    key: u64,
+    counter: u64,
+}
+
+impl PixelRng {
+    pub fn new(seed: u64, pixel: u64, sample: u64) -> PixelRng {
+        PixelRng::from_key(mix(
+            seed ^ mix(pixel ^ mix(sample.wrapping_add(GOLDEN_GAMMA)))
+        ))
+    }
+
+    // A stream for any other value that identifies what is being sampled.
+    pub fn from_key(key: u64) -> PixelRng {
+        PixelRng { key, counter: 0 }
+    }
+}
+
+impl Rng for PixelRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.counter += 1;
+        mix(self
+            .key
+            .wrapping_add(self.counter.wrapping_mul(GOLDEN_GAMMA)))
+    }
+}
@@ -0,0 +1,529 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use glam::{Vec2, Vec3A};
+use rand::Rng;
+
+use crate::rng::PixelRng;
+
+// The random numbers of one pixel's samples. Every random choice along a path takes the next
+// dimension, so with a well distributed sampler the pixel jitter, lens, time and bounce
+// directions of different samples cover their domains evenly instead of clumping.
+pub trait Sampler {
+    // Starts the index-th sample of the pixel, going back to the first dimension.
+    fn start_sample(&mut self, index: usize);
+
+    // A number in [0, 1).
+    fn next_1d(&mut self) -> f32;
+
+    // A point in [0, 1)^2, both dimensions from the same well distributed set.
+    fn next_2d(&mut self) -> Vec2;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerKind {
+    // A sampler for one pixel, samples_per_pixel is how many samples it will be asked for.
+    pub fn create(self, seed: u64, pixel: u64, samples_per_pixel: usize) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed, pixel)),
+            SamplerKind::Stratified => {
+                Box::new(StratifiedSampler::new(seed, pixel, samples_per_pixel))
+            }
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed, pixel)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed, pixel)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SamplerKind, String> {
+        match s {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!(
+                "unknown sampler `{}`, expected independent, stratified, halton or sobol",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SamplerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Uniform direction from a point in [0, 1)^2.
+pub fn uniform_sphere(u: Vec2) -> Vec3A {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    Vec3A::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Uniform point in the unit disk at z = 0, keeping the strata of u intact.
+pub fn concentric_disk(u: Vec2) -> Vec3A {
+    let offset = 2.0 * u - Vec2::ONE;
+    if offset == Vec2::ZERO {
+        return Vec3A::ZERO;
+    }
+
+    let (r, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, PI / 4.0 * (offset.y / offset.x))
+    } else {
+        (offset.y, PI / 2.0 - PI / 4.0 * (offset.x / offset.y))
+    };
+    Vec3A::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+fn hash(a: u64, b: u64) -> u64 {
+    let mut rng = PixelRng::from_key(a ^ b.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    rng.next_u64()
+}
+
+fn to_unit_float(bits: u32) -> f32 {
+    // The top 24 bits, so the result stays below 1.
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+// Every number is independent, the same as the renderer always had.
+#[derive(Debug)]
+pub struct IndependentSampler {
+    seed: u64,
+    pixel: u64,
+    rng: PixelRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64, pixel: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            pixel,
+            rng: PixelRng::new(seed, pixel, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.rng = PixelRng::new(self.seed, self.pixel, index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.rng.gen()
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        Vec2::new(self.rng.gen(), self.rng.gen())
+    }
+}
+
+// Kensler's hashed permutation of i in [0, l), from "Correlated Multi-Jittered Sampling".
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    if l <= 1 {
+        return 0;
+    }
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    // Reduced first, a wrapping add would break the permutation when l isn't a power of two.
+    (i + p % l) % l
+}
+
+// Splits every dimension into one stratum per sample and jitters inside it, 2D requests use a
+// grid. Which sample gets which stratum is shuffled per pixel and dimension.
+#[derive(Debug)]
+pub struct StratifiedSampler {
+    seed: u64,
+    pixel: u64,
+    samples_per_pixel: usize,
+    index: usize,
+    dimension: u64,
+    rng: PixelRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, pixel: u64, samples_per_pixel: usize) -> StratifiedSampler {
+        StratifiedSampler {
+            seed,
+            pixel,
+            samples_per_pixel: samples_per_pixel.max(1),
+            index: 0,
+            dimension: 0,
+            rng: PixelRng::new(seed, pixel, 0),
+        }
+    }
+
+    // The stratum of the current sample out of count, for the next dimension.
+    fn next_stratum(&mut self, count: usize) -> usize {
+        let scramble = hash(hash(self.seed, self.pixel), self.dimension) as u32;
+        self.dimension += 1;
+        let index = (self.index % self.samples_per_pixel) as u32;
+        permute(index, count as u32, scramble) as usize
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.index = index;
+        self.dimension = 0;
+        self.rng = PixelRng::new(self.seed, self.pixel, index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let n = self.samples_per_pixel;
+        let stratum = self.next_stratum(n);
+        (stratum as f32 + self.rng.gen::<f32>()) / n as f32
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        // Samples counts that aren't square leave some cells of the grid empty.
+        let n = self.samples_per_pixel;
+        let columns = ((n as f32).sqrt() as usize).max(1);
+        let rows = n.div_ceil(columns);
+        let cell = self.next_stratum(columns * rows);
+        let jitter = Vec2::new(self.rng.gen(), self.rng.gen());
+        Vec2::new(
+            ((cell % columns) as f32 + jitter.x) / columns as f32,
+            ((cell / columns) as f32 + jitter.y) / rows as f32,
+        )
+    }
+}
+
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+fn radical_inverse(base: u32, mut index: u64) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut result = 0.0;
+    let mut scale = inv_base;
+    while index > 0 {
+        result += (index % base as u64) as f64 * scale;
+        index /= base as u64;
+        scale *= inv_base;
+    }
+    result as f32
+}
+
+// The Halton sequence, one prime base per dimension, shifted by a random offset per pixel and
+// dimension so neighbouring pixels don't share the same pattern. Past the table of primes the
+// numbers are independent.
+#[derive(Debug)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    index: usize,
+    dimension: usize,
+    rng: PixelRng,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64, pixel: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            pixel,
+            index: 0,
+            dimension: 0,
+            rng: PixelRng::new(seed, pixel, 0),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.index = index;
+        self.dimension = 0;
+        self.rng = PixelRng::new(self.seed, self.pixel, index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        if dimension >= PRIMES.len() {
+            return self.rng.gen();
+        }
+
+        let offset = to_unit_float(hash(hash(self.seed, self.pixel), dimension as u64) as u32);
+        let value = radical_inverse(PRIMES[dimension], self.index as u64) + offset;
+        value.fract().min(1.0 - f32::EPSILON)
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        Vec2::new(self.next_1d(), self.next_1d())
+    }
+}
+
+// The first two Sobol dimensions: van der Corput and the one for the polynomial x + 1.
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    let mut result = 0;
+    let mut v = 1u32 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v = if dimension == 0 { v >> 1 } else { v ^ (v >> 1) };
+    }
+    result
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// Owen scrambled Sobol points, after Burley's "Practical Hash-based Owen Scrambling". Every
+// request uses the first Sobol dimensions with its own scramble and shuffled index, which
+// keeps each pair well distributed without a table of direction numbers.
+#[derive(Debug)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel: u64,
+    index: usize,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64, pixel: u64) -> SobolSampler {
+        SobolSampler {
+            seed,
+            pixel,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_point(&mut self) -> (u32, u32) {
+        let scramble = hash(hash(self.seed, self.pixel), self.dimension);
+        self.dimension += 1;
+
+        let index = nested_uniform_scramble(self.index as u32, scramble as u32);
+        let x = nested_uniform_scramble(sobol(index, 0), hash(scramble, 0) as u32);
+        let y = nested_uniform_scramble(sobol(index, 1), hash(scramble, 1) as u32);
+        (x, y)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        to_unit_float(self.next_point().0)
+    }
+
+    fn next_2d(&mut self) -> Vec2 {
+        let (x, y) = self.next_point();
+        Vec2::new(to_unit_float(x), to_unit_float(y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    // Sample by sample, alternating 1D and 2D requests for dimensions numbers per sample.
+    fn draw(sampler: &mut dyn Sampler, samples: usize, dimensions: usize) -> Vec<Vec<f32>> {
+        (0..samples)
+            .map(|i| {
+                sampler.start_sample(i);
+                let mut values = Vec::new();
+                while values.len() < dimensions {
+                    if values.len() % 3 == 0 {
+                        values.push(sampler.next_1d());
+                    } else {
+                        let u = sampler.next_2d();
+                        values.extend([u.x, u.y]);
+                    }
+                }
+                values
+            })
+            .collect()
+    }
+
+    // The interval of size 1 / count each value falls in.
+    fn strata(values: impl Iterator<Item = f32>, count: usize) -> Vec<usize> {
+        let mut strata: Vec<usize> = values.map(|v| (v * count as f32) as usize).collect();
+        strata.sort_unstable();
+        strata
+    }
+
+    #[test]
+    fn values_are_in_the_unit_interval() {
+        for kind in KINDS {
+            for pixel in 0..8 {
+                // More dimensions than there are Halton primes.
+                for sample in draw(&mut *kind.create(3, pixel, 37), 37, 100) {
+                    for v in sample {
+                        assert!((0.0..1.0).contains(&v), "{}: {}", kind, v);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_and_pixel_give_the_same_samples() {
+        for kind in KINDS {
+            let a = draw(&mut *kind.create(7, 12, 16), 16, 10);
+            assert_eq!(a, draw(&mut *kind.create(7, 12, 16), 16, 10), "{}", kind);
+            assert_ne!(a, draw(&mut *kind.create(7, 13, 16), 16, 10), "{}", kind);
+            assert_ne!(a, draw(&mut *kind.create(8, 12, 16), 16, 10), "{}", kind);
+
+            // Starting a sample over repeats it.
+            let mut sampler = kind.create(7, 12, 16);
+            draw(&mut *sampler, 16, 10);
+            assert_eq!(draw(&mut *sampler, 1, 10)[0], a[0], "{}", kind);
+        }
+    }
+
+    #[test]
+    fn permute_is_a_permutation() {
+        for l in [1, 2, 3, 7, 16, 100, 1000] {
+            for p in [0, 1, 0xdead_beef, u32::MAX] {
+                let mut values: Vec<u32> = (0..l).map(|i| permute(i, l, p)).collect();
+                values.sort_unstable();
+                assert_eq!(values, (0..l).collect::<Vec<_>>(), "l {} p {}", l, p);
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_samples_get_one_stratum_each() {
+        for n in [1, 5, 16, 30] {
+            let samples = draw(&mut StratifiedSampler::new(5, 9, n), n, 12);
+            for dimension in (0..12).step_by(3) {
+                let strata = strata(samples.iter().map(|s| s[dimension]), n);
+                assert_eq!(strata, (0..n).collect::<Vec<_>>(), "n {}", n);
+            }
+
+            // Each 2D request takes its own grid cell.
+            let columns = (n as f32).sqrt() as usize;
+            let rows = n.div_ceil(columns);
+            for dimension in [1, 4, 7, 10] {
+                let mut cells: Vec<usize> = samples
+                    .iter()
+                    .map(|s| {
+                        let column = (s[dimension] * columns as f32) as usize;
+                        let row = (s[dimension + 1] * rows as f32) as usize;
+                        row * columns + column
+                    })
+                    .collect();
+                cells.sort_unstable();
+                cells.dedup();
+                assert_eq!(cells.len(), n, "n {}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_points_are_a_net() {
+        for pixel in 0..4 {
+            for k in [0, 1, 4, 8] {
+                let n = 1 << k;
+                let samples = draw(&mut SobolSampler::new(11, pixel), n, 12);
+                for dimension in 0..12 {
+                    let strata = strata(samples.iter().map(|s| s[dimension]), n);
+                    assert_eq!(strata, (0..n).collect::<Vec<_>>(), "k {}", k);
+                }
+
+                // Every 2^-a by 2^-(k - a) box of a 2D request holds one point.
+                for dimension in [1, 4, 7, 10] {
+                    for a in 0..=k {
+                        let mut cells: Vec<usize> = samples
+                            .iter()
+                            .map(|s| {
+                                let x = (s[dimension] * (1 << a) as f32) as usize;
+                                let y = (s[dimension + 1] * (1 << (k - a)) as f32) as usize;
+                                y << a | x
+                            })
+                            .collect();
+                        cells.sort_unstable();
+                        assert_eq!(cells, (0..n).collect::<Vec<_>>(), "k {} a {}", k, a);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn halton_matches_the_radical_inverse() {
+        assert_eq!(radical_inverse(2, 0), 0.0);
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert_eq!(radical_inverse(3, 5), 7.0 / 9.0);
+
+        // The per pixel offset is a rotation, so the first base^k samples of a dimension still
+        // hit each 1 / base^k interval once.
+        let samples = draw(&mut HaltonSampler::new(2, 3), 27, 2);
+        let strata = strata(samples.iter().map(|s| s[1]), 27);
+        assert_eq!(strata, (0..27).collect::<Vec<_>>());
+    }
+}
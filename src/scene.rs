@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use glam::Vec3A;
+use rand::{Rng, SeedableRng, StdRng};
+
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::environment::{Environment, GradientEnvironment};
+use crate::hittable::Hittable;
+use crate::material::Material;
+use crate::moving_sphere::MovingSphere;
+use crate::rect::XZRect;
+use crate::scene_file::{self, SceneError};
+use crate::settings::RenderSettings;
+use crate::sphere::Sphere;
+use crate::texture::{CheckerTexture, SolidColor, Texture};
+use crate::world::World;
+
+// Everything the renderer needs: the objects, already in a Bvh, and the camera looking at them.
+#[derive(Debug)]
+pub struct Scene {
+    bvh: Bvh,
+    camera: Camera,
+    environment: Box<dyn Environment>,
+    // The emissive objects, also in the Bvh.
+    lights: Vec<Arc<dyn Hittable>>,
+}
+
+impl Scene {
+    pub fn new(world: World, camera: Camera) -> Scene {
+        let (t0, t1) = camera.shutter();
+        let lights = world.lights().to_vec();
+        Scene {
+            bvh: world.generate_bvh(t0, t1),
+            camera,
+            environment: Box::<GradientEnvironment>::default(),
+            lights,
+        }
+    }
+
+    // What rays that miss everything see, the sky gradient by default. Scenes lit only by
+    // emissive materials want a black SolidEnvironment here.
+    pub fn with_environment(mut self, environment: Box<dyn Environment>) -> Scene {
+        self.environment = environment;
+        self
+    }
+
+    // Moving the camera keeps the Bvh, it has to use the same shutter times.
+    pub fn set_camera(&mut self, camera: Camera) {
+        assert_eq!(
+            camera.shutter(),
+            self.camera.shutter(),
+            "camera shutter changed"
+        );
+        self.camera = camera;
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, settings: &RenderSettings) -> Result<Scene, SceneError> {
+        scene_file::load(path, settings)
+    }
+
+    // The final scene of "Raytracing in one weekend", with the small spheres bouncing.
+    pub fn random_spheres(settings: &RenderSettings) -> Scene {
+        let mut rng = StdRng::from_seed(&[settings.seed as usize]);
+        let mut world = World::default();
+        world.add_object(Box::new(XZRect::new(
+            -1000.0,
+            1000.0,
+            -1000.0,
+            1000.0,
+            0.0,
+            Material::Lambertian {
+                texture: Arc::new(Box::new(CheckerTexture::new(
+                    Vec3A::new(0.2, 0.3, 0.1),
+                    Vec3A::new(0.9, 0.9, 0.9),
+                ))),
+            },
+        )));
+
+        for a in -11..11 {
+            for b in -11..11 {
+                let choose_mat = rng.gen_range::<f32>(0.0, 1.0);
+
+                let center = Vec3A::new(
+                    a as f32 + 0.9 * rng.gen_range(0.0, 1.0),
+                    0.2 + 0.2 * rng.gen_range::<f32>(0.0, 1.0),
+                    b as f32 + 0.9 * rng.gen_range(0.0, 1.0),
+                );
+
+                if (center - Vec3A::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                    if choose_mat < 0.8 {
+                        let r: f32 = rng.gen_range(0.0, 1.0) * rng.gen_range(0.0, 1.0);
+                        let g: f32 = rng.gen_range(0.0, 1.0) * rng.gen_range(0.0, 1.0);
+                        let b: f32 = rng.gen_range(0.0, 1.0) * rng.gen_range(0.0, 1.0);
+                        let center2 = center + 0.4 * Vec3A::new(0.0, rng.gen_range(0.0, 0.5), 0.0);
+                        world.add_object(Box::new(MovingSphere::new(
+                            center,
+                            center2,
+                            0.0,
+                            1.0,
+                            0.2,
+                            Material::Lambertian {
+                                texture: Arc::new(Box::new(SolidColor::new(Vec3A::new(r, g, b)))),
+                            },
+                        )));
+                    } else if choose_mat < 0.95 {
+                        let r: f32 = 0.5 * (1.0 + rng.gen_range(0.0, 1.0));
+                        let g: f32 = 0.5 * (1.0 + rng.gen_range(0.0, 1.0));
+                        let b: f32 = 0.5 * (1.0 + rng.gen_range(0.0, 1.0));
+                        world.add_object(Box::new(Sphere::new(
+                            center,
+                            0.2,
+                            Material::Metal {
+                                albedo: Vec3A::new(r, g, b),
+                                fuzz: 0.5 * rng.gen_range(0.0, 1.0),
+                            },
+                        )));
+                    } else {
+                        world.add_object(Box::new(Sphere::new(
+                            center,
+                            0.2,
+                            Material::Dielectric { ref_idx: 1.5 },
+                        )));
+                    }
+                }
+            }
+        }
+
+        let texture: Arc<Box<dyn Texture>> =
+            Arc::new(Box::new(SolidColor::new(Vec3A::new(0.4, 0.2, 0.1))));
+
+        world.add_object(Box::new(Sphere::new(
+            Vec3A::new(0.0, 1.0, 0.0),
+            1.0,
+            Material::Dielectric { ref_idx: 1.5 },
+        )));
+
+        world.add_object(Box::new(Sphere::new(
+            Vec3A::new(-4.0, 1.0, 0.0),
+            1.0,
+            Material::Lambertian { texture },
+        )));
+
+        world.add_object(Box::new(Sphere::new(
+            Vec3A::new(4.0, 1.0, 0.0),
+            1.0,
+            Material::Metal {
+                albedo: Vec3A::new(0.7, 0.6, 0.5),
+                fuzz: 0.0,
+            },
+        )));
+
+        let look_from = Vec3A::new(12.0, 1.0, 3.0);
+        let look_at = Vec3A::new(1.0, 0.7, -1.0);
+        let apperture = 0.0;
+        let dist_to_focus = 10.0;
+        let camera = Camera::new(
+            look_from,
+            look_at,
+            Vec3A::new(0.0, 1.0, 0.0),
+            20.0,
+            settings.aspect(),
+            apperture,
+            dist_to_focus,
+            0.0,
+            1.0,
+        );
+
+        Scene::new(world, camera)
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
+    pub fn lights(&self) -> &[Arc<dyn Hittable>] {
+        &self.lights
+    }
+}
@@ -0,0 +1,900 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::{Affine3A, Vec2, Vec3A};
+use image::ImageError;
+use ron::extensions::Extensions;
+use serde::Deserialize;
+
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::constant_medium::ConstantMedium;
+use crate::environment::{
+    Environment, EnvironmentMap, GradientEnvironment, PhysicalSky, SolidEnvironment,
+};
+use crate::hittable::Hittable;
+use crate::material::{Material, MetalIor};
+use crate::moving_sphere::MovingSphere;
+use crate::obj::{self, ObjError, ObjMesh};
+use crate::rect::{BoxShape, XYRect, XZRect, YZRect};
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::sphere::Sphere;
+use crate::texture::{
+    CheckerTexture, Filter, ImageTexture, NoisePattern, NoiseTexture, SolidColor, Texture, WrapMode,
+};
+use crate::transform::Transformed;
+use crate::triangle::{Triangle, TriangleMesh};
+use crate::world::World;
+
+// A scene is a RON file, for example:
+//
+// (
+//     camera: (look_from: (13.0, 2.0, 3.0), look_at: (0.0, 0.0, 0.0), vfov: 20.0),
+//     objects: [
+//         XZRect(
+//             x0: -1000.0, x1: 1000.0, z0: -1000.0, z1: 1000.0, k: 0.0,
+//             material: Lambertian(texture: Checker(odd: (0.2, 0.3, 0.1), even: (0.9, 0.9, 0.9))),
+//         ),
+//         Sphere(center: (0.0, 1.0, 0.0), radius: 1.0, material: Dielectric(ref_idx: 1.5)),
+//     ],
+// )
+//
+// See scenes/ for complete examples.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraDesc,
+    // Solid color seen by rays that miss, short for environment: SolidColor(..).
+    #[serde(default)]
+    pub background: Option<Vec3A>,
+    // What rays that miss see, the sky gradient when neither this nor background is set.
+    #[serde(default)]
+    pub environment: Option<EnvironmentDesc>,
+    pub objects: Vec<ObjectDesc>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDesc {
+    pub look_from: Vec3A,
+    pub look_at: Vec3A,
+    #[serde(default = "default_up")]
+    pub up: Vec3A,
+    pub vfov: f32,
+    #[serde(default)]
+    pub aperture: f32,
+    // Defaults to the distance between look_from and look_at.
+    #[serde(default)]
+    pub focus_dist: Option<f32>,
+    #[serde(default)]
+    pub time0: f32,
+    #[serde(default = "default_time1")]
+    pub time1: f32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub enum ObjectDesc {
+    Sphere {
+        center: Vec3A,
+        radius: f32,
+        material: MaterialDesc,
+    },
+    MovingSphere {
+        center0: Vec3A,
+        center1: Vec3A,
+        time0: f32,
+        time1: f32,
+        radius: f32,
+        material: MaterialDesc,
+    },
+    Triangle {
+        vertices: [Vec3A; 3],
+        #[serde(default)]
+        normals: Option<[Vec3A; 3]>,
+        #[serde(default)]
+        uvs: Option<[Vec2; 3]>,
+        material: MaterialDesc,
+    },
+    // normals and uvs are optional, when given they need one entry per position. Fixed size
+    // arrays are tuples in RON, so indices are written as [(0, 1, 2), (2, 1, 3)].
+    Mesh {
+        positions: Vec<Vec3A>,
+        #[serde(default)]
+        normals: Vec<Vec3A>,
+        #[serde(default)]
+        uvs: Vec<Vec2>,
+        indices: Vec<[u32; 3]>,
+        material: MaterialDesc,
+    },
+    // Axis aligned rectangles, e.g. XYRect spans x0..x1, y0..y1 at z = k. The normal points
+    // along the positive axis unless flip is set.
+    XYRect {
+        x0: f32,
+        x1: f32,
+        y0: f32,
+        y1: f32,
+        k: f32,
+        #[serde(default)]
+        flip: bool,
+        material: MaterialDesc,
+    },
+    XZRect {
+        x0: f32,
+        x1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        #[serde(default)]
+        flip: bool,
+        material: MaterialDesc,
+    },
+    YZRect {
+        y0: f32,
+        y1: f32,
+        z0: f32,
+        z1: f32,
+        k: f32,
+        #[serde(default)]
+        flip: bool,
+        material: MaterialDesc,
+    },
+    Box {
+        min: Vec3A,
+        max: Vec3A,
+        material: MaterialDesc,
+    },
+    // Places another object. It is scaled first, then rotated around x, y and z
+    // (in degrees), then translated.
+    Transform {
+        #[serde(default)]
+        translate: Vec3A,
+        #[serde(default)]
+        rotate: Vec3A,
+        #[serde(default = "default_scale")]
+        scale: Vec3A,
+        object: Box<ObjectDesc>,
+    },
+    // Fog or smoke filling the closed boundary object.
+    ConstantMedium {
+        boundary: Box<ObjectDesc>,
+        density: f32,
+        albedo: TextureDesc,
+    },
+    // A Wavefront OBJ file, materials come from its MTL files unless material is set.
+    Obj {
+        path: PathBuf,
+        #[serde(default)]
+        material: Option<MaterialDesc>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub enum MaterialDesc {
+    Lambertian { texture: TextureDesc },
+    Metal { albedo: Vec3A, fuzz: f32 },
+    Dielectric { ref_idx: f32 },
+    DiffuseLight { emit: TextureDesc },
+    Isotropic { albedo: TextureDesc },
+    // ior is Gold, Copper, Aluminium, Silver or Custom(eta: (r, g, b), k: (r, g, b)).
+    Conductor { ior: MetalIor, roughness: f32 },
+    RoughDielectric { ref_idx: f32, roughness: f32 },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub enum TextureDesc {
+    SolidColor(Vec3A),
+    Checker {
+        odd: Vec3A,
+        even: Vec3A,
+    },
+    // A PNG, JPEG or HDR image, relative to the scene file.
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        wrap: WrapMode,
+        #[serde(default)]
+        filter: Filter,
+    },
+    // Perlin noise, the pattern blends from low to high. The same seed always gives the
+    // same noise.
+    Noise {
+        #[serde(default)]
+        pattern: NoisePattern,
+        #[serde(default = "default_noise_scale")]
+        scale: f32,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        low: Vec3A,
+        #[serde(default = "default_high")]
+        high: Vec3A,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub enum EnvironmentDesc {
+    SolidColor(Vec3A),
+    Gradient {
+        #[serde(default = "default_bottom")]
+        bottom: Vec3A,
+        #[serde(default = "default_top")]
+        top: Vec3A,
+    },
+    // An equirectangular image, relative to the scene file. rotation turns it around the
+    // y axis, in degrees.
+    Map {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+    },
+    // A daylight sky lit by a sun in sun_direction. The sun disc itself, sun_radius in degrees,
+    // is only drawn when sun_intensity is set.
+    PhysicalSky {
+        sun_direction: Vec3A,
+        #[serde(default = "default_turbidity")]
+        turbidity: f32,
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+        #[serde(default = "default_sun_radius")]
+        sun_radius: f32,
+        #[serde(default)]
+        sun_intensity: f32,
+    },
+}
+
+fn default_up() -> Vec3A {
+    Vec3A::new(0.0, 1.0, 0.0)
+}
+
+fn default_scale() -> Vec3A {
+    Vec3A::ONE
+}
+
+fn default_time1() -> f32 {
+    1.0
+}
+
+fn default_noise_scale() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    7
+}
+
+fn default_high() -> Vec3A {
+    Vec3A::ONE
+}
+
+fn default_bottom() -> Vec3A {
+    Vec3A::ONE
+}
+
+fn default_top() -> Vec3A {
+    Vec3A::new(0.5, 0.7, 1.0)
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_sun_radius() -> f32 {
+    0.25
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+    Obj(ObjError),
+    Image {
+        path: PathBuf,
+        error: ImageError,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            SceneError::Invalid { path, message } => write!(f, "{}: {}", path.display(), message),
+            SceneError::Obj(error) => write!(f, "{}", error),
+            SceneError::Image { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl TextureDesc {
+    fn build(&self, base_dir: &Path) -> Result<Arc<Box<dyn Texture>>, SceneError> {
+        let texture: Box<dyn Texture> = match self {
+            TextureDesc::SolidColor(color) => Box::new(SolidColor::new(*color)),
+            TextureDesc::Checker { odd, even } => Box::new(CheckerTexture::new(*odd, *even)),
+            TextureDesc::Image { path, wrap, filter } => {
+                let path = base_dir.join(path);
+                let image =
+                    ImageTexture::load(&path).map_err(|error| SceneError::Image { path, error })?;
+                Box::new(image.with_wrap(*wrap).with_filter(*filter))
+            }
+            TextureDesc::Noise {
+                pattern,
+                scale,
+                seed,
+                octaves,
+                low,
+                high,
+            } => Box::new(
+                NoiseTexture::new(*seed, *scale, *pattern)
+                    .with_octaves(*octaves)
+                    .with_colors(*low, *high),
+            ),
+        };
+
+        Ok(Arc::new(texture))
+    }
+}
+
+impl EnvironmentDesc {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            EnvironmentDesc::PhysicalSky {
+                sun_direction,
+                turbidity,
+                ..
+            } => {
+                if *sun_direction == Vec3A::ZERO {
+                    return Err("sun_direction can't be zero".to_string());
+                }
+                if *turbidity < 1.0 {
+                    return Err("turbidity must be at least 1".to_string());
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn build(&self, base_dir: &Path) -> Result<Box<dyn Environment>, SceneError> {
+        let environment: Box<dyn Environment> = match self {
+            EnvironmentDesc::SolidColor(color) => Box::new(SolidEnvironment::new(*color)),
+            EnvironmentDesc::Gradient { bottom, top } => {
+                Box::new(GradientEnvironment::new(*bottom, *top))
+            }
+            EnvironmentDesc::Map {
+                path,
+                rotation,
+                intensity,
+            } => {
+                let path = base_dir.join(path);
+                let image =
+                    ImageTexture::load(&path).map_err(|error| SceneError::Image { path, error })?;
+                Box::new(
+                    EnvironmentMap::new(image)
+                        .with_rotation(rotation.to_radians())
+                        .with_intensity(*intensity),
+                )
+            }
+            EnvironmentDesc::PhysicalSky {
+                sun_direction,
+                turbidity,
+                intensity,
+                sun_radius,
+                sun_intensity,
+            } => Box::new(
+                PhysicalSky::new(*sun_direction, *turbidity)
+                    .with_intensity(*intensity)
+                    .with_sun(*sun_radius, *sun_intensity),
+            ),
+        };
+
+        Ok(environment)
+    }
+}
+
+impl MaterialDesc {
+    fn build(&self, base_dir: &Path) -> Result<Material, SceneError> {
+        let material = match self {
+            MaterialDesc::Lambertian { texture } => Material::Lambertian {
+                texture: texture.build(base_dir)?,
+            },
+            MaterialDesc::Metal { albedo, fuzz } => Material::Metal {
+                albedo: *albedo,
+                fuzz: *fuzz,
+            },
+            MaterialDesc::Dielectric { ref_idx } => Material::Dielectric { ref_idx: *ref_idx },
+            MaterialDesc::DiffuseLight { emit } => Material::DiffuseLight {
+                emit: emit.build(base_dir)?,
+            },
+            MaterialDesc::Isotropic { albedo } => Material::Isotropic {
+                albedo: albedo.build(base_dir)?,
+            },
+            MaterialDesc::Conductor { ior, roughness } => Material::conductor(*ior, *roughness),
+            MaterialDesc::RoughDielectric { ref_idx, roughness } => Material::RoughDielectric {
+                ref_idx: *ref_idx,
+                roughness: *roughness,
+            },
+        };
+
+        Ok(material)
+    }
+}
+
+impl ObjectDesc {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ObjectDesc::Sphere {
+                radius, material, ..
+            } => {
+                if *radius <= 0.0 && matches!(material, MaterialDesc::DiffuseLight { .. }) {
+                    return Err("light sphere needs a positive radius".to_string());
+                }
+                Ok(())
+            }
+            ObjectDesc::Mesh {
+                positions,
+                normals,
+                uvs,
+                indices,
+                ..
+            } => {
+                if indices.is_empty() {
+                    return Err("mesh has no triangles".to_string());
+                }
+                if !normals.is_empty() && normals.len() != positions.len() {
+                    return Err("mesh needs one normal per position".to_string());
+                }
+                if !uvs.is_empty() && uvs.len() != positions.len() {
+                    return Err("mesh needs one uv per position".to_string());
+                }
+                if let Some(index) = indices
+                    .iter()
+                    .flatten()
+                    .find(|&&index| index as usize >= positions.len())
+                {
+                    return Err(format!(
+                        "mesh index {} out of range, there are {} positions",
+                        index,
+                        positions.len()
+                    ));
+                }
+                Ok(())
+            }
+            ObjectDesc::XYRect { x0, x1, y0, y1, .. } => validate_rect(*x0, *x1, *y0, *y1),
+            ObjectDesc::XZRect { x0, x1, z0, z1, .. } => validate_rect(*x0, *x1, *z0, *z1),
+            ObjectDesc::YZRect { y0, y1, z0, z1, .. } => validate_rect(*y0, *y1, *z0, *z1),
+            ObjectDesc::Transform { scale, object, .. } => {
+                if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+                    return Err("transform scale can't be zero".to_string());
+                }
+                object.validate()
+            }
+            ObjectDesc::ConstantMedium {
+                boundary, density, ..
+            } => {
+                if *density <= 0.0 {
+                    return Err("medium density must be positive".to_string());
+                }
+                boundary.validate()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Emissive shapes that can be sampled directly, see Hittable::sample_surface. Other
+    // emissive objects, like triangles, meshes, boxes or OBJ models with Ke, still glow but
+    // only light the scene through the paths that happen to hit them.
+    fn is_light(&self) -> bool {
+        match self {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::XYRect { material, .. }
+            | ObjectDesc::XZRect { material, .. }
+            | ObjectDesc::YZRect { material, .. } => {
+                matches!(material, MaterialDesc::DiffuseLight { .. })
+            }
+            ObjectDesc::Transform { object, .. } => object.is_light(),
+            _ => false,
+        }
+    }
+
+    // Whether the object's own material is a DiffuseLight, OBJ files with Ke are checked
+    // when they are loaded.
+    fn is_emissive(&self) -> bool {
+        match self {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::MovingSphere { material, .. }
+            | ObjectDesc::Triangle { material, .. }
+            | ObjectDesc::Mesh { material, .. }
+            | ObjectDesc::XYRect { material, .. }
+            | ObjectDesc::XZRect { material, .. }
+            | ObjectDesc::YZRect { material, .. }
+            | ObjectDesc::Box { material, .. }
+            | ObjectDesc::Obj {
+                material: Some(material),
+                ..
+            } => matches!(material, MaterialDesc::DiffuseLight { .. }),
+            ObjectDesc::Transform { object, .. } => object.is_emissive(),
+            _ => false,
+        }
+    }
+
+    // Relative paths are resolved against base_dir, the folder of the scene file.
+    fn build(&self, base_dir: &Path, objs: &mut ObjCache) -> Result<Box<dyn Hittable>, SceneError> {
+        let object: Box<dyn Hittable> = match self {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => Box::new(Sphere::new(*center, *radius, material.build(base_dir)?)),
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => Box::new(MovingSphere::new(
+                *center0,
+                *center1,
+                *time0,
+                *time1,
+                *radius,
+                material.build(base_dir)?,
+            )),
+            ObjectDesc::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let mut triangle = Triangle::new(*vertices, material.build(base_dir)?);
+                if let Some(normals) = normals {
+                    triangle = triangle.with_normals(*normals);
+                }
+                if let Some(uvs) = uvs {
+                    triangle = triangle.with_uvs(*uvs);
+                }
+                Box::new(triangle)
+            }
+            ObjectDesc::Mesh {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material,
+            } => Box::new(TriangleMesh::new(
+                positions.clone(),
+                normals.clone(),
+                uvs.clone(),
+                indices.clone(),
+                material.build(base_dir)?,
+            )),
+            ObjectDesc::XYRect {
+                x0,
+                x1,
+                y0,
+                y1,
+                k,
+                flip,
+                material,
+            } => {
+                let rect = XYRect::new(*x0, *x1, *y0, *y1, *k, material.build(base_dir)?);
+                Box::new(if *flip { rect.flipped() } else { rect })
+            }
+            ObjectDesc::XZRect {
+                x0,
+                x1,
+                z0,
+                z1,
+                k,
+                flip,
+                material,
+            } => {
+                let rect = XZRect::new(*x0, *x1, *z0, *z1, *k, material.build(base_dir)?);
+                Box::new(if *flip { rect.flipped() } else { rect })
+            }
+            ObjectDesc::YZRect {
+                y0,
+                y1,
+                z0,
+                z1,
+                k,
+                flip,
+                material,
+            } => {
+                let rect = YZRect::new(*y0, *y1, *z0, *z1, *k, material.build(base_dir)?);
+                Box::new(if *flip { rect.flipped() } else { rect })
+            }
+            ObjectDesc::Box { min, max, material } => {
+                Box::new(BoxShape::new(*min, *max, material.build(base_dir)?))
+            }
+            ObjectDesc::Transform {
+                translate,
+                rotate,
+                scale,
+                object,
+            } => {
+                let transform = Affine3A::from_translation((*translate).into())
+                    * Affine3A::from_rotation_z(rotate.z.to_radians())
+                    * Affine3A::from_rotation_y(rotate.y.to_radians())
+                    * Affine3A::from_rotation_x(rotate.x.to_radians())
+                    * Affine3A::from_scale((*scale).into());
+                Box::new(Transformed::new(object.build(base_dir, objs)?, transform))
+            }
+            ObjectDesc::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => Box::new(ConstantMedium::new(
+                boundary.build(base_dir, objs)?,
+                *density,
+                albedo.build(base_dir)?,
+            )),
+            ObjectDesc::Obj { path, material } => {
+                let meshes = match objs.entry(base_dir.join(path)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let model = obj::load_obj(entry.key()).map_err(SceneError::Obj)?;
+                        for warning in &model.warnings {
+                            eprintln!("warning: {}", warning);
+                        }
+                        let emissive = model
+                            .meshes
+                            .iter()
+                            .any(|mesh| matches!(mesh.material, Material::DiffuseLight { .. }));
+                        if material.is_none() && emissive {
+                            eprintln!(
+                                "warning: {}: emissive (Ke) materials are not sampled as lights",
+                                entry.key().display()
+                            );
+                        }
+                        entry.insert(model.meshes)
+                    }
+                };
+
+                let material = material
+                    .as_ref()
+                    .map(|material| material.build(base_dir))
+                    .transpose()?;
+                let meshes: Vec<Box<dyn Hittable>> = meshes
+                    .iter()
+                    .cloned()
+                    .map(|mut mesh| {
+                        if let Some(material) = &material {
+                            mesh.material = material.clone();
+                        }
+                        Box::new(mesh.into_triangle_mesh()) as Box<dyn Hittable>
+                    })
+                    .collect();
+                Box::new(Bvh::new(meshes, 0.0, 0.0))
+            }
+        };
+
+        Ok(object)
+    }
+}
+
+// OBJ files already loaded while building a scene, so a model placed several times is only
+// read, and warned about, once.
+type ObjCache = HashMap<PathBuf, Vec<ObjMesh>>;
+
+// A rectangle without area is never hit, and can't be sampled as a light.
+fn validate_rect(a0: f32, a1: f32, b0: f32, b1: f32) -> Result<(), String> {
+    if a0 == a1 || b0 == b1 {
+        return Err("rectangle has no area".to_string());
+    }
+    Ok(())
+}
+
+impl SceneFile {
+    pub fn parse(path: &Path, source: &str) -> Result<SceneFile, SceneError> {
+        // Lets optional fields be written without wrapping them in Some(..).
+        let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+        options.from_str(source).map_err(|e| SceneError::Parse {
+            path: path.to_path_buf(),
+            line: e.position.line,
+            column: e.position.col,
+            message: e.code.to_string(),
+        })
+    }
+
+    // Catches what would otherwise panic while building the objects.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.objects.is_empty() {
+            return Err("scene has no objects".to_string());
+        }
+
+        let camera = &self.camera;
+        if camera.look_from == camera.look_at {
+            return Err("camera look_from and look_at can't be the same point".to_string());
+        }
+        // The camera takes its right axis from up x forward, which is zero then. Also catches
+        // an up of (0, 0, 0).
+        let forward = (camera.look_at - camera.look_from).normalize();
+        if camera.up.normalize_or_zero().cross(forward).length() < 1e-4 {
+            return Err("camera up can't be parallel to the view direction".to_string());
+        }
+
+        if self.background.is_some() && self.environment.is_some() {
+            return Err("set either background or environment, not both".to_string());
+        }
+        if let Some(environment) = &self.environment {
+            environment
+                .validate()
+                .map_err(|message| format!("environment: {}", message))?;
+        }
+
+        for (i, object) in self.objects.iter().enumerate() {
+            object
+                .validate()
+                .map_err(|message| format!("object {}: {}", i, message))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn build(&self, base_dir: &Path, settings: &RenderSettings) -> Result<Scene, SceneError> {
+        let mut world = World::default();
+        let mut objs = ObjCache::new();
+        for (i, object) in self.objects.iter().enumerate() {
+            if object.is_light() {
+                world.add_light(object.build(base_dir, &mut objs)?);
+            } else {
+                if object.is_emissive() {
+                    eprintln!("warning: object {}: emissive but not sampled as a light", i);
+                }
+                world.add_object(object.build(base_dir, &mut objs)?);
+            }
+        }
+
+        let c = &self.camera;
+        let camera = Camera::new(
+            c.look_from,
+            c.look_at,
+            c.up,
+            c.vfov,
+            settings.aspect(),
+            c.aperture,
+            c.focus_dist
+                .unwrap_or_else(|| (c.look_from - c.look_at).length()),
+            c.time0,
+            c.time1,
+        );
+
+        let scene = Scene::new(world, camera);
+        let environment = match (&self.environment, self.background) {
+            (Some(environment), _) => environment.build(base_dir)?,
+            (None, Some(background)) => Box::new(SolidEnvironment::new(background)),
+            (None, None) => return Ok(scene),
+        };
+
+        Ok(scene.with_environment(environment))
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P, settings: &RenderSettings) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| SceneError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    let scene = SceneFile::parse(path, &source)?;
+    scene.validate().map_err(|message| SceneError::Invalid {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    scene.build(path.parent().unwrap_or_else(|| Path::new("")), settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<SceneFile, SceneError> {
+        SceneFile::parse(Path::new("test.ron"), source)
+    }
+
+    // A scene with the given camera and a single object, objects written in RON.
+    fn validate(camera: &str, object: &str) -> Result<(), String> {
+        let source = format!("(camera: ({}, vfov: 40.0), objects: [{}])", camera, object);
+        parse(&source).unwrap().validate()
+    }
+
+    const CAMERA: &str = "look_from: (0.0, 1.0, 5.0), look_at: (0.0, 1.0, 0.0)";
+    const SPHERE: &str =
+        "Sphere(center: (0.0, 1.0, 0.0), radius: 1.0, material: Dielectric(ref_idx: 1.5))";
+
+    #[test]
+    fn parse_errors_point_at_the_line_and_column() {
+        let source = "(
+    camera: (look_from: (0.0, 1.0, 5.0), look_at: (0.0, 1.0, 0.0), vfov: 40.0),
+    objects: [Cone(radius: 1.0)],
+)";
+        match parse(source) {
+            Err(SceneError::Parse {
+                path, line, column, ..
+            }) => {
+                assert_eq!(path, Path::new("test.ron"));
+                // Just past the name of the unknown object.
+                assert_eq!((line, column), (3, 19));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn valid_scene_passes() {
+        assert_eq!(validate(CAMERA, SPHERE), Ok(()));
+    }
+
+    #[test]
+    fn invalid_cameras_are_rejected() {
+        let cameras = [
+            "look_from: (0.0, 1.0, 0.0), look_at: (0.0, 1.0, 0.0)",
+            "look_from: (0.0, 5.0, 0.0), look_at: (0.0, 0.0, 0.0)",
+            "look_from: (0.0, 0.0, 0.0), look_at: (0.0, 3.0, 0.0), up: (0.0, -2.0, 0.0)",
+            "look_from: (0.0, 1.0, 5.0), look_at: (0.0, 1.0, 0.0), up: (0.0, 0.0, 0.0)",
+        ];
+        for camera in cameras {
+            assert!(validate(camera, SPHERE).is_err(), "{}", camera);
+        }
+    }
+
+    #[test]
+    fn invalid_objects_are_rejected() {
+        let objects = [
+            "XZRect(x0: 0.0, x1: 0.0, z0: -1.0, z1: 1.0, k: 0.0, material: Dielectric(ref_idx: 1.5))",
+            "Sphere(center: (0.0, 0.0, 0.0), radius: 0.0, material: DiffuseLight(emit: SolidColor((4.0, 4.0, 4.0))))",
+            "Mesh(positions: [(0.0, 0.0, 0.0)], indices: [], material: Dielectric(ref_idx: 1.5))",
+            "Mesh(positions: [(0.0, 0.0, 0.0)], indices: [(0, 1, 2)], material: Dielectric(ref_idx: 1.5))",
+            "Transform(scale: (1.0, 0.0, 1.0), object: Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: Dielectric(ref_idx: 1.5)))",
+            "ConstantMedium(boundary: Sphere(center: (0.0, 0.0, 0.0), radius: 1.0, material: Dielectric(ref_idx: 1.5)), density: 0.0, albedo: SolidColor((1.0, 1.0, 1.0)))",
+        ];
+        for object in objects {
+            let error = validate(CAMERA, object).unwrap_err();
+            assert!(error.starts_with("object 0: "), "{}: {}", object, error);
+        }
+    }
+
+    #[test]
+    fn empty_scenes_are_rejected() {
+        assert!(validate(CAMERA, "").is_err());
+    }
+}
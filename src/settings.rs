@@ -0,0 +1,76 @@
+use crate::sampler::SamplerKind;
+use crate::tiles::TileOrder;
+
+// Keeps sampling noisy pixels after the first RenderSettings::samples, until the standard error
+// of their mean luminance drops below threshold times the luminance, or they reach max_samples.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveSampling {
+    pub max_samples: usize,
+    pub threshold: f32,
+}
+
+// How many bounces of each kind a path can take. Paths are mostly ended earlier by russian
+// roulette, these only cap paths that keep their throughput, like ones stuck inside glass.
+#[derive(Clone, Copy, Debug)]
+pub struct BounceLimits {
+    pub diffuse: u32,
+    pub specular: u32,
+    pub transmission: u32,
+}
+
+impl Default for BounceLimits {
+    fn default() -> BounceLimits {
+        BounceLimits {
+            diffuse: 16,
+            specular: 64,
+            transmission: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples: usize,
+    pub bounces: BounceLimits,
+    // Paths can be ended by russian roulette after this many bounces, with a probability that
+    // grows as their throughput drops.
+    pub roulette_depth: u32,
+    // Picks the random scene, and the random numbers of every sample. The same seed always
+    // renders the same image.
+    pub seed: u64,
+    // Index of the first sample of each pixel, so progressive passes continue the per-pixel
+    // random streams instead of repeating them.
+    pub first_sample: usize,
+    pub sampler: SamplerKind,
+    // Width and height of the square tiles the image is split into.
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    // When set, samples is the minimum number of samples per pixel.
+    pub adaptive: Option<AdaptiveSampling>,
+}
+
+impl RenderSettings {
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            width: 640,
+            height: 320,
+            samples: 5,
+            bounces: BounceLimits::default(),
+            roulette_depth: 3,
+            seed: 0,
+            first_sample: 0,
+            sampler: SamplerKind::default(),
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            adaptive: None,
+        }
+    }
+}
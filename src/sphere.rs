@@ -1,10 +1,12 @@
 use std::f32::consts::PI;
 
 use crate::aabb::AABB;
-use crate::hittable::{HitRecord, Hittable};
+use crate::helpers::orthonormal_basis;
+use crate::hittable::{self, HitRecord, Hittable, SurfaceSample};
 use crate::material::Material;
 use crate::ray::Ray;
-use glam::Vec3A;
+use crate::sampler::uniform_sphere;
+use glam::{Vec2, Vec3A};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -16,12 +18,23 @@ pub struct Sphere {
 impl Sphere {
     pub fn new(center: Vec3A, radius: f32, material: Material) -> Sphere {
         Sphere {
-            center: center,
-            radius: radius,
-            material: material,
+            center,
+            radius,
+            material,
         }
     }
 
+    // The cosine of the half angle of the cone the sphere covers seen from origin, None from
+    // inside.
+    fn cos_theta_max(&self, origin: Vec3A) -> Option<f32> {
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+
     pub fn get_uv(p: &Vec3A) -> (f32, f32) {
         let theta = f32::acos(-p.y);
         let phi = f32::atan2(-p.z, p.x) + PI;
@@ -47,26 +60,28 @@ impl Hittable for Sphere {
             let temp = (-b - (b * b - a * c).sqrt()) / a;
             if temp < t_max && temp > t_min {
                 let p = ray.point_at(temp);
-                let (u, v) = Sphere::get_uv(&p);
+                let normal = (p - self.center) / self.radius;
+                let (u, v) = Sphere::get_uv(&normal);
                 return Some(HitRecord {
                     t: temp,
                     p,
                     u,
                     v,
-                    normal: (p - self.center) / self.radius,
+                    normal,
                     material: Some(self.material.clone()),
                 });
             }
             let temp = (-b + (b * b - a * c).sqrt()) / a;
             if temp < t_max && temp > t_min {
                 let p = ray.point_at(temp);
-                let (u, v) = Sphere::get_uv(&p);
+                let normal = (p - self.center) / self.radius;
+                let (u, v) = Sphere::get_uv(&normal);
                 return Some(HitRecord {
                     t: temp,
                     p,
                     u,
                     v,
-                    normal: (p - self.center) / self.radius,
+                    normal,
                     material: Some(self.material.clone()),
                 });
             }
@@ -79,4 +94,48 @@ impl Hittable for Sphere {
         let r = Vec3A::new(self.radius, self.radius, self.radius);
         AABB::new(self.center - r, self.center + r)
     }
+
+    fn sample_surface(&self, u: Vec2) -> Option<SurfaceSample> {
+        let normal = uniform_sphere(u);
+        Some(SurfaceSample {
+            p: self.center + self.radius * normal,
+            normal,
+            pdf: self.surface_pdf(Vec3A::ZERO, normal),
+        })
+    }
+
+    fn surface_pdf(&self, _p: Vec3A, _normal: Vec3A) -> f32 {
+        1.0 / (4.0 * PI * self.radius * self.radius)
+    }
+
+    // Seen from outside, directions are picked uniformly in the cone the sphere covers, none
+    // of them miss it. From inside it falls back to picking points on the surface.
+    fn pdf_value(&self, origin: Vec3A, dir: Vec3A, time: f32) -> f32 {
+        let cos_theta_max = match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return hittable::area_pdf_value(self, origin, dir, time),
+        };
+
+        if self
+            .hit(&Ray::new(origin, dir, time), 0.001, f32::MAX)
+            .is_none()
+        {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random_direction(&self, origin: Vec3A, u: Vec2) -> Option<Vec3A> {
+        let cos_theta_max = match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return self.sample_surface(u).map(|sample| sample.p - origin),
+        };
+
+        let z = 1.0 + u.y * (cos_theta_max - 1.0);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.x;
+        let w = (self.center - origin).normalize();
+        let (a, b) = orthonormal_basis(w);
+        Some(r * phi.cos() * a + r * phi.sin() * b + z * w)
+    }
 }
@@ -1,4 +1,13 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
 use glam::Vec3A;
+use image::codecs::hdr::HdrDecoder;
+use image::{ColorType, ImageError, ImageFormat};
+use serde::Deserialize;
+
+use crate::perlin::Perlin;
 
 pub trait Texture: Send + Sync + core::fmt::Debug {
     fn color(&self, u: f32, v: f32, p: Vec3A) -> Vec3A;
@@ -21,6 +30,8 @@ impl Texture for SolidColor {
     }
 }
 
+// Cells in space centered on the origin, so planes through it like a floor at y = 0 fall in
+// the middle of a row of cells instead of on the edge between two.
 #[derive(Debug)]
 pub struct CheckerTexture {
     odd: Vec3A,
@@ -34,13 +45,262 @@ impl CheckerTexture {
 }
 
 impl Texture for CheckerTexture {
-    fn color(&self, u: f32, v: f32, p: Vec3A) -> Vec3A {
-        let sines = f32::sin(20.0 * p.x) * f32::sin(10.0 * p.y) * f32::sin(10.0 * p.z);
+    fn color(&self, _: f32, _: f32, p: Vec3A) -> Vec3A {
+        let cosines = f32::cos(20.0 * p.x) * f32::cos(10.0 * p.y) * f32::cos(10.0 * p.z);
 
-        if sines < 0.0 {
+        if cosines < 0.0 {
             self.odd
         } else {
             self.even
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let period = i.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+        };
+        wrapped as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// An image sampled at the hit's u, v, with (0, 0) at the bottom left. Pixels are stored as
+// linear color, 8 and 16 bit images are converted from sRGB when loaded while float
+// formats like HDR are assumed to already be linear.
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3A>,
+    wrap_u: WrapMode,
+    wrap_v: WrapMode,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    // pixels are linear and stored top row first.
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3A>) -> ImageTexture {
+        assert_eq!(pixels.len(), width * height, "ImageTexture size mismatch");
+        ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap_u: WrapMode::default(),
+            wrap_v: WrapMode::default(),
+            filter: Filter::default(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ImageTexture, ImageError> {
+        let path = path.as_ref();
+        // image::open squeezes HDR files into 8 bits, clipping everything brighter than 1.
+        if ImageFormat::from_path(path)? == ImageFormat::Hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .iter()
+                .map(|p| Vec3A::new(p[0], p[1], p[2]))
+                .collect();
+            return Ok(ImageTexture::new(
+                metadata.width as usize,
+                metadata.height as usize,
+                pixels,
+            ));
+        }
+
+        let image = image::open(path)?;
+        let is_linear = matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+        let rgb = image.into_rgb32f();
+
+        let pixels = rgb
+            .pixels()
+            .map(|p| {
+                let c = Vec3A::new(p[0], p[1], p[2]);
+                if is_linear {
+                    c
+                } else {
+                    Vec3A::new(
+                        srgb_to_linear(c.x),
+                        srgb_to_linear(c.y),
+                        srgb_to_linear(c.z),
+                    )
+                }
+            })
+            .collect();
+
+        Ok(ImageTexture::new(
+            rgb.width() as usize,
+            rgb.height() as usize,
+            pixels,
+        ))
+    }
+
+    pub fn with_wrap(self, wrap: WrapMode) -> ImageTexture {
+        self.with_wrap_uv(wrap, wrap)
+    }
+
+    // Separate modes along u and v, e.g. an environment map wraps around horizontally but
+    // not over the poles.
+    pub fn with_wrap_uv(mut self, wrap_u: WrapMode, wrap_v: WrapMode) -> ImageTexture {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> ImageTexture {
+        self.filter = filter;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // The unfiltered pixel, row 0 is the top of the image.
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3A {
+        self.pixels[y * self.width + x]
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3A {
+        let x = self.wrap_u.apply(x, self.width);
+        let y = self.wrap_v.apply(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn color(&self, u: f32, v: f32, _: Vec3A) -> Vec3A {
+        // Pixel centers are at half integer coordinates.
+        let x = u * self.width as f32;
+        let y = (1.0 - v) * self.height as f32;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let tx = x - x0;
+                let ty = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum NoisePattern {
+    // Plain Perlin noise.
+    #[default]
+    Noise,
+    // fBm, several octaves of noise summed together.
+    Turbulence,
+    // Stripes along z distorted by turbulence.
+    Marble,
+    // Rings around the y axis distorted by turbulence.
+    Wood,
+}
+
+// Procedural Perlin noise textures. The pattern gives a value in [0, 1] at the hit point,
+// which blends between the low and high colors.
+#[derive(Debug)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f32,
+    pattern: NoisePattern,
+    octaves: u32,
+    low: Vec3A,
+    high: Vec3A,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, scale: f32, pattern: NoisePattern) -> NoiseTexture {
+        NoiseTexture {
+            perlin: Perlin::new(seed),
+            scale,
+            pattern,
+            octaves: 7,
+            low: Vec3A::ZERO,
+            high: Vec3A::ONE,
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> NoiseTexture {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn with_colors(mut self, low: Vec3A, high: Vec3A) -> NoiseTexture {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    fn value(&self, p: Vec3A) -> f32 {
+        // For marble and wood the scale sets the frequency of the stripes and rings, the
+        // turbulence bending them stays at the unscaled point.
+        let value = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.perlin.noise(self.scale * p)),
+            NoisePattern::Turbulence => self.perlin.turbulence(self.scale * p, self.octaves),
+            NoisePattern::Marble => {
+                let turbulence = self.perlin.turbulence(p, self.octaves);
+                0.5 * (1.0 + f32::sin(self.scale * p.z + 10.0 * turbulence))
+            }
+            NoisePattern::Wood => {
+                let radius = (p.x * p.x + p.z * p.z).sqrt();
+                (self.scale * radius + self.perlin.turbulence(p, self.octaves)).fract()
+            }
+        };
+        value.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn color(&self, _: f32, _: f32, p: Vec3A) -> Vec3A {
+        self.low.lerp(self.high, self.value(p))
+    }
+}
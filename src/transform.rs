@@ -0,0 +1,90 @@
+use glam::{Affine3A, Mat3A, Vec2, Vec3A};
+
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable, SurfaceSample};
+use crate::ray::Ray;
+
+// Places any Hittable in the world with an affine transform, so the same object can be
+// instanced at several positions, rotations and scales.
+#[derive(Debug)]
+pub struct Transformed<H: Hittable> {
+    object: H,
+    transform: Affine3A,
+    inverse: Affine3A,
+    normal_matrix: Mat3A,
+}
+
+impl<H: Hittable> Transformed<H> {
+    pub fn new(object: H, transform: Affine3A) -> Transformed<H> {
+        let inverse = transform.inverse();
+        Transformed {
+            object,
+            transform,
+            inverse,
+            normal_matrix: inverse.matrix3.transpose(),
+        }
+    }
+
+    pub fn transform(&self) -> Affine3A {
+        self.transform
+    }
+
+    // How much the transform stretches a bit of surface with the given object space normal.
+    fn area_scale(&self, local_normal: Vec3A) -> f32 {
+        self.transform.matrix3.determinant().abs() * (self.normal_matrix * local_normal).length()
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let origin = self.inverse.transform_point3a(ray.origin());
+        let dir = self.inverse.transform_vector3a(ray.dir());
+
+        // Ray::new normalizes the direction, so distances in object space are
+        // scaled by the length of the transformed direction.
+        let scale = dir.length();
+        let local_ray = Ray::new(origin, dir, ray.time()).with_scatter_sample(ray.scatter_sample());
+
+        let mut rec = self.object.hit(&local_ray, t_min * scale, t_max * scale)?;
+        rec.t /= scale;
+        rec.p = self.transform.transform_point3a(rec.p);
+        rec.normal = (self.normal_matrix * rec.normal).normalize();
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> AABB {
+        let bb = self.object.bounding_box(t0, t1);
+        let mut min = Vec3A::splat(f32::MAX);
+        let mut max = Vec3A::splat(f32::MIN);
+
+        for i in 0..8 {
+            let corner = Vec3A::new(
+                if i & 1 == 0 { bb.min.x } else { bb.max.x },
+                if i & 2 == 0 { bb.min.y } else { bb.max.y },
+                if i & 4 == 0 { bb.min.z } else { bb.max.z },
+            );
+            let p = self.transform.transform_point3a(corner);
+            min = min.min(p);
+            max = max.max(p);
+        }
+
+        AABB::new(min, max)
+    }
+
+    fn sample_surface(&self, u: Vec2) -> Option<SurfaceSample> {
+        let sample = self.object.sample_surface(u)?;
+        Some(SurfaceSample {
+            p: self.transform.transform_point3a(sample.p),
+            normal: (self.normal_matrix * sample.normal).normalize(),
+            pdf: sample.pdf / self.area_scale(sample.normal),
+        })
+    }
+
+    fn surface_pdf(&self, p: Vec3A, normal: Vec3A) -> f32 {
+        let local_normal = (self.transform.matrix3.transpose() * normal).normalize();
+        let pdf = self
+            .object
+            .surface_pdf(self.inverse.transform_point3a(p), local_normal);
+        pdf / self.area_scale(local_normal)
+    }
+}
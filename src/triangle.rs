@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3A};
+
+use crate::aabb::AABB;
+use crate::bvh::Bvh;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+fn bounding_box(p0: Vec3A, p1: Vec3A, p2: Vec3A) -> AABB {
+    AABB::padded(p0.min(p1).min(p2), p0.max(p1).max(p2))
+}
+
+// Möller-Trumbore. Returns the distance along the ray and the barycentric
+// coordinates of the hit for p1 and p2.
+fn intersect(
+    p0: Vec3A,
+    p1: Vec3A,
+    p2: Vec3A,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = ray.dir().cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - p0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.dir().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if t < t_max && t > t_min {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct Triangle {
+    vertices: [Vec3A; 3],
+    normals: Option<[Vec3A; 3]>,
+    uvs: Option<[Vec2; 3]>,
+    material: Material,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3A; 3], material: Material) -> Triangle {
+        Triangle {
+            vertices,
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3A; 3]) -> Triangle {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [Vec2; 3]) -> Triangle {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let [p0, p1, p2] = self.vertices;
+        let (t, b1, b2) = intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let normal = match self.normals {
+            Some([n0, n1, n2]) => (b0 * n0 + b1 * n1 + b2 * n2).normalize(),
+            None => (p1 - p0).cross(p2 - p0).normalize(),
+        };
+        let uv = match self.uvs {
+            Some([uv0, uv1, uv2]) => b0 * uv0 + b1 * uv1 + b2 * uv2,
+            None => Vec2::new(b1, b2),
+        };
+
+        Some(HitRecord {
+            t,
+            p: ray.point_at(t),
+            normal,
+            u: uv.x,
+            v: uv.y,
+            material: Some(self.material.clone()),
+        })
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> AABB {
+        let [p0, p1, p2] = self.vertices;
+        bounding_box(p0, p1, p2)
+    }
+}
+
+#[derive(Debug)]
+struct MeshData {
+    positions: Vec<Vec3A>,
+    normals: Vec<Vec3A>,
+    uvs: Vec<Vec2>,
+    indices: Vec<[u32; 3]>,
+    material: Material,
+}
+
+impl MeshData {
+    fn positions(&self, triangle: usize) -> (Vec3A, Vec3A, Vec3A) {
+        let [i0, i1, i2] = self.indices[triangle];
+        (
+            self.positions[i0 as usize],
+            self.positions[i1 as usize],
+            self.positions[i2 as usize],
+        )
+    }
+}
+
+// One triangle of a TriangleMesh, only stores its index so the vertex data stays shared.
+#[derive(Debug)]
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mesh = &self.mesh;
+        let (p0, p1, p2) = mesh.positions(self.index);
+        let (t, b1, b2) = intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+        let [i0, i1, i2] = mesh.indices[self.index].map(|i| i as usize);
+
+        let normal = if mesh.normals.is_empty() {
+            (p1 - p0).cross(p2 - p0).normalize()
+        } else {
+            (b0 * mesh.normals[i0] + b1 * mesh.normals[i1] + b2 * mesh.normals[i2]).normalize()
+        };
+        let uv = if mesh.uvs.is_empty() {
+            Vec2::new(b1, b2)
+        } else {
+            b0 * mesh.uvs[i0] + b1 * mesh.uvs[i1] + b2 * mesh.uvs[i2]
+        };
+
+        Some(HitRecord {
+            t,
+            p: ray.point_at(t),
+            normal,
+            u: uv.x,
+            v: uv.y,
+            material: Some(mesh.material.clone()),
+        })
+    }
+
+    fn bounding_box(&self, _: f32, _: f32) -> AABB {
+        let (p0, p1, p2) = self.mesh.positions(self.index);
+        bounding_box(p0, p1, p2)
+    }
+}
+
+// An indexed triangle mesh. Normals and uvs are either empty or have one entry per position,
+// and are interpolated across each triangle. Without uvs the barycentric coordinates of the
+// hit are used as u, v. The triangles get their own Bvh so the mesh can be added to a World
+// as a single object.
+#[derive(Debug)]
+pub struct TriangleMesh {
+    bvh: Bvh,
+    triangle_count: usize,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Vec3A>,
+        normals: Vec<Vec3A>,
+        uvs: Vec<Vec2>,
+        indices: Vec<[u32; 3]>,
+        material: Material,
+    ) -> TriangleMesh {
+        assert!(
+            !indices.is_empty(),
+            "TriangleMesh needs at least one triangle"
+        );
+        assert!(
+            normals.is_empty() || normals.len() == positions.len(),
+            "TriangleMesh needs one normal per position"
+        );
+        assert!(
+            uvs.is_empty() || uvs.len() == positions.len(),
+            "TriangleMesh needs one uv per position"
+        );
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < positions.len()),
+            "TriangleMesh index out of range"
+        );
+
+        let triangle_count = indices.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        });
+
+        let triangles: Vec<Box<dyn Hittable>> = (0..triangle_count)
+            .map(|index| {
+                Box::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    index,
+                }) as Box<dyn Hittable>
+            })
+            .collect();
+
+        TriangleMesh {
+            bvh: Bvh::new(triangles, 0.0, 0.0),
+            triangle_count,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_count
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> AABB {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use glam::Vec3A;
 
 use crate::aabb::AABB;
@@ -9,6 +11,7 @@ use std::boxed::Box;
 #[derive(Default, Debug)]
 pub struct World {
     objects: Vec<Box<dyn Hittable>>,
+    lights: Vec<Arc<dyn Hittable>>,
 }
 
 impl World {
@@ -16,6 +19,18 @@ impl World {
         self.objects.push(obj);
     }
 
+    // Adds an emissive object that the renderer also samples directly, instead of only
+    // finding it by bouncing into it.
+    pub fn add_light(&mut self, light: Box<dyn Hittable>) {
+        let light: Arc<dyn Hittable> = Arc::from(light);
+        self.objects.push(Box::new(light.clone()));
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[Arc<dyn Hittable>] {
+        &self.lights
+    }
+
     pub fn generate_bvh(self, t0: f32, t1: f32) -> Bvh {
         Bvh::new(self.objects, t0, t1)
     }